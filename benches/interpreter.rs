@@ -0,0 +1,77 @@
+//! End-to-end timings for representative programs, run through `lox_tree_rust::run`.
+//!
+//! `cargo bench` runs every workload; `cargo bench -- fib` runs only those whose
+//! name contains "fib". Each workload is run several times on a fresh interpreter
+//! and the fastest and median times are reported.
+
+use std::env;
+use std::io;
+use std::time::{Duration, Instant};
+
+use lox_tree_rust::Interpreter;
+
+const RUNS: usize = 10;
+
+fn workloads() -> Vec<(&'static str, String)> {
+    vec![
+        (
+            "counting loop",
+            "var i = 0;
+             while (i < 1000000) i = i + 1;"
+                .to_string(),
+        ),
+        (
+            "recursive fibonacci",
+            "fun fib(n) {
+                 if (n < 2) return n;
+                 return fib(n - 1) + fib(n - 2);
+             }
+             print fib(25);"
+                .to_string(),
+        ),
+        (
+            "string building",
+            "var s = \"\";
+             for (var i = 0; i < 20000; i = i + 1) s = s + \"x\";
+             print s == reverse(s);"
+                .to_string(),
+        ),
+        // Scanning used to be quadratic in the source length
+        ("large source", "var a = 1;\n".repeat(100_000)),
+    ]
+}
+
+fn main() {
+    // cargo passes flags such as --bench through, so only bare words filter
+    let filters: Vec<String> = env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+
+    for (name, source) in workloads() {
+        if !filters.is_empty() && !filters.iter().any(|filter| name.contains(filter.as_str())) {
+            continue;
+        }
+        let mut times: Vec<Duration> = (0..RUNS).map(|_| time_run(name, &source)).collect();
+        times.sort();
+        println!(
+            "{:<20} fastest {:>10.2?}   median {:>10.2?}",
+            name,
+            times[0],
+            times[RUNS / 2]
+        );
+    }
+}
+
+fn time_run(name: &str, source: &str) -> Duration {
+    let mut interpreter = Interpreter::new().with_output(Box::new(io::sink()));
+    let start = Instant::now();
+    let result = lox_tree_rust::run(source, &mut interpreter);
+    let elapsed = start.elapsed();
+    assert!(
+        matches!(result, Ok(None)) && !interpreter.error_reporter.had_runtime_error(),
+        "workload '{}' failed",
+        name
+    );
+    elapsed
+}
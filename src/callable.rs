@@ -1,43 +1,282 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
 use crate::{
-    interpreter::{Interpreter, RuntimeError},
-    tokens::Object,
+    class::LoxInstance,
+    environment::Environment,
+    interpreter::{Interpreter, RuntimeError, Unwind},
+    statements::Stmt,
+    tokens::{Object, Token, TokenType},
 };
 
-pub trait Callable {
+pub trait Callable: fmt::Debug + fmt::Display {
+    /// `paren` is the closing parenthesis of the call, used to locate errors.
     fn call(
-        &self,
+        self: Rc<Self>,
         interpreter: &mut Interpreter,
+        paren: &Token,
         args: Vec<Object>,
-    ) -> Result<Object, RuntimeError>;
+    ) -> Result<Object, Unwind>;
+
+    fn arity(&self) -> Arity;
+}
+
+/// How many arguments a callable accepts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exact(usize),
+    AtLeast(usize),
+    /// Inclusive at both ends.
+    Range(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match self {
+            Arity::Exact(n) => count == *n,
+            Arity::AtLeast(n) => count >= *n,
+            Arity::Range(min, max) => (*min..=*max).contains(&count),
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arity::Exact(n) => write!(f, "{}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::Range(min, max) => write!(f, "{} to {}", min, max),
+        }
+    }
+}
+
+/// Natives report failures as a message; the call site supplies the location.
+pub type NativeFn = fn(&mut Interpreter, Vec<Object>) -> Result<Object, NativeError>;
+
+#[derive(Debug)]
+pub enum NativeError {
+    Message(String),
+    /// Raised by Lox code the native ran, such as a `toString` method, and
+    /// passed on as is.
+    Unwind(Unwind),
+}
+
+impl From<String> for NativeError {
+    fn from(message: String) -> Self {
+        NativeError::Message(message)
+    }
+}
 
-    fn arity(&self) -> usize;
+impl From<Unwind> for NativeError {
+    fn from(unwind: Unwind) -> Self {
+        NativeError::Unwind(unwind)
+    }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct NativeCallable {
-    arity: usize,
-    func: fn(&mut Interpreter, Vec<Object>) -> Result<Object, RuntimeError>,
+    arity: Arity,
+    func: NativeFn,
 }
 
 impl NativeCallable {
-    pub fn new(
-        arity: usize,
-        func: fn(&mut Interpreter, Vec<Object>) -> Result<Object, RuntimeError>,
-    ) -> Self {
+    pub fn new(arity: Arity, func: NativeFn) -> Self {
         Self { arity, func }
     }
 }
 
+impl fmt::Display for NativeCallable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native fn>")
+    }
+}
+
 impl Callable for NativeCallable {
     fn call(
-        &self,
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        args: Vec<Object>,
+    ) -> Result<Object, Unwind> {
+        (self.func)(interpreter, args).map_err(|error| match error {
+            NativeError::Message(message) => Unwind::Error(RuntimeError {
+                message,
+                token: paren.clone(),
+            }),
+            NativeError::Unwind(unwind) => unwind,
+        })
+    }
+
+    fn arity(&self) -> Arity {
+        self.arity
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LoxFunction {
+    name: Token,
+    // Shared so that binding a method doesn't copy its body
+    params: Rc<[Token]>,
+    body: Rc<[Stmt]>,
+    // The scope the function was declared in
+    closure: Rc<RefCell<Environment>>,
+    // Initializers always return the instance they were called on
+    is_initializer: bool,
+}
+
+impl LoxFunction {
+    pub fn new(
+        name: Token,
+        params: Rc<[Token]>,
+        body: Rc<[Stmt]>,
+        closure: Rc<RefCell<Environment>>,
+        is_initializer: bool,
+    ) -> Self {
+        Self {
+            name,
+            params,
+            body,
+            closure,
+            is_initializer,
+        }
+    }
+
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+
+    /// Returns a copy of this method with `this` bound to `instance`.
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        environment.define(&self.this_token(), Object::Instance(instance));
+        LoxFunction {
+            closure: Rc::new(RefCell::new(environment)),
+            ..self.clone()
+        }
+    }
+
+    fn this_token(&self) -> Token {
+        Token::new(TokenType::This, "this".to_string(), None, self.name.line)
+    }
+}
+
+impl fmt::Display for LoxFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.name.lexeme)
+    }
+}
+
+impl Callable for LoxFunction {
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        _paren: &Token,
+        args: Vec<Object>,
+    ) -> Result<Object, Unwind> {
+        let mut environment = Environment::with_enclosing(Rc::clone(&self.closure));
+        for (param, arg) in self.params.iter().zip(args) {
+            environment.define(param, arg);
+        }
+        match interpreter.execute_function_body(&self.body, environment) {
+            Ok(()) | Err(Unwind::Return(_)) if self.is_initializer => {
+                Ok(self.closure.borrow().get(&self.this_token())?)
+            }
+            Ok(()) => Ok(Object::Nil),
+            Err(Unwind::Return(value)) => Ok(value),
+            // Loops in the caller don't see a break from inside the function
+            Err(Unwind::Break(keyword) | Unwind::Continue(keyword)) => {
+                Err(Unwind::outside_loop(keyword).into())
+            }
+            Err(unwind) => Err(unwind),
+        }
+    }
+
+    fn arity(&self) -> Arity {
+        Arity::Exact(self.params.len())
+    }
+}
+
+/// `compose(outer, inner)`: calls `inner` with the arguments, then `outer`
+/// with what it returned.
+#[derive(Debug)]
+pub struct Composed {
+    outer: Rc<dyn Callable>,
+    inner: Rc<dyn Callable>,
+}
+
+impl Composed {
+    pub fn new(outer: Rc<dyn Callable>, inner: Rc<dyn Callable>) -> Self {
+        Self { outer, inner }
+    }
+}
+
+impl fmt::Display for Composed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {} . {}>", self.outer, self.inner)
+    }
+}
+
+impl Callable for Composed {
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        args: Vec<Object>,
+    ) -> Result<Object, Unwind> {
+        let value = Rc::clone(&self.inner).call(interpreter, paren, args)?;
+        Rc::clone(&self.outer).call(interpreter, paren, vec![value])
+    }
+
+    fn arity(&self) -> Arity {
+        self.inner.arity()
+    }
+}
+
+/// `partial(func, a, b)`: calls `func` with `a` and `b` ahead of whatever
+/// arguments it is given.
+#[derive(Debug)]
+pub struct Partial {
+    func: Rc<dyn Callable>,
+    bound: Vec<Object>,
+    arity: Arity,
+}
+
+impl Partial {
+    /// Fails if `func` can't take that many arguments.
+    pub fn new(func: Rc<dyn Callable>, bound: Vec<Object>) -> Option<Self> {
+        let count = bound.len();
+        let arity = match func.arity() {
+            Arity::Exact(n) if n >= count => Arity::Exact(n - count),
+            Arity::AtLeast(n) => Arity::AtLeast(n.saturating_sub(count)),
+            Arity::Range(min, max) if max >= count => {
+                Arity::Range(min.saturating_sub(count), max - count)
+            }
+            _ => return None,
+        };
+        Some(Self { func, bound, arity })
+    }
+}
+
+impl fmt::Display for Partial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<partial {}>", self.func)
+    }
+}
+
+impl Callable for Partial {
+    fn call(
+        self: Rc<Self>,
         interpreter: &mut Interpreter,
+        paren: &Token,
         args: Vec<Object>,
-    ) -> Result<Object, RuntimeError> {
-        (self.func)(interpreter, args)
+    ) -> Result<Object, Unwind> {
+        let mut all = self.bound.clone();
+        all.extend(args);
+        Rc::clone(&self.func).call(interpreter, paren, all)
     }
 
-    fn arity(&self) -> usize {
+    fn arity(&self) -> Arity {
         self.arity
     }
 }
@@ -0,0 +1,128 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::{
+    callable::{Arity, Callable, LoxFunction},
+    interpreter::{Interpreter, RuntimeError, Unwind},
+    tokens::{Object, Token},
+};
+
+#[derive(Debug)]
+pub struct LoxClass {
+    name: String,
+    superclass: Option<Rc<LoxClass>>,
+    methods: HashMap<String, LoxFunction>,
+}
+
+impl LoxClass {
+    pub fn new(
+        name: String,
+        superclass: Option<Rc<LoxClass>>,
+        methods: HashMap<String, LoxFunction>,
+    ) -> Self {
+        Self {
+            name,
+            superclass,
+            methods,
+        }
+    }
+
+    /// Looks the method up on this class, then on each superclass in turn.
+    pub fn find_method(&self, name: &str) -> Option<&LoxFunction> {
+        self.methods.get(name).or_else(|| {
+            self.superclass
+                .as_ref()
+                .and_then(|superclass| superclass.find_method(name))
+        })
+    }
+}
+
+impl fmt::Display for LoxClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+/// Calling a class constructs a new instance and runs `init` on it, if defined.
+impl Callable for LoxClass {
+    fn call(
+        self: Rc<Self>,
+        interpreter: &mut Interpreter,
+        paren: &Token,
+        args: Vec<Object>,
+    ) -> Result<Object, Unwind> {
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&self))));
+        if let Some(initializer) = self.find_method("init") {
+            Rc::new(initializer.bind(Rc::clone(&instance))).call(interpreter, paren, args)?;
+        }
+        Ok(Object::Instance(instance))
+    }
+
+    fn arity(&self) -> Arity {
+        self.find_method("init")
+            .map_or(Arity::Exact(0), |initializer| initializer.arity())
+    }
+}
+
+#[derive(Debug)]
+pub struct LoxInstance {
+    class: Rc<LoxClass>,
+    fields: HashMap<String, Object>,
+}
+
+impl LoxInstance {
+    pub fn new(class: Rc<LoxClass>) -> Self {
+        Self {
+            class,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Fields shadow methods. Methods are returned bound to `instance`.
+    pub fn get(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> Result<Object, RuntimeError> {
+        let this = instance.borrow();
+        if let Some(value) = this.fields.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+        if let Some(method) = this.class.find_method(&name.lexeme) {
+            return Ok(Object::Callable(Rc::new(method.bind(Rc::clone(instance)))));
+        }
+
+        Err(RuntimeError {
+            message: format!("Undefined property '{}'.", name.lexeme),
+            token: name.clone(),
+        })
+    }
+
+    pub fn set(&mut self, name: &Token, value: Object) {
+        self.fields.insert(name.lexeme.clone(), value);
+    }
+
+    /// Renders the instance with its class's `toString` method, falling back to
+    /// `Name instance` when the class doesn't define one.
+    pub fn stringify(
+        instance: &Rc<RefCell<LoxInstance>>,
+        interpreter: &mut Interpreter,
+    ) -> Result<String, Unwind> {
+        let method = instance.borrow().class.find_method("toString").cloned();
+        let Some(method) = method else {
+            return Ok(instance.borrow().to_string());
+        };
+        let name = method.name().clone();
+        match interpreter.call(Rc::new(method.bind(Rc::clone(instance))), &name, Vec::new())? {
+            Object::String(text) => Ok(text),
+            _ => Err(RuntimeError {
+                message: "toString must return a string.".to_string(),
+                token: name,
+            })?,
+        }
+    }
+}
+
+impl fmt::Display for LoxInstance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} instance", self.class.name)
+    }
+}
@@ -1,71 +1,128 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::interpreter::RuntimeError;
 use crate::tokens::{Object, Token};
 
-pub struct EnvironmentStack {
-    environments: Vec<HashMap<String, Object>>,
+/// A single scope. Scopes are chained through `enclosing` so that functions can
+/// hold on to the scope they were declared in.
+#[derive(Debug)]
+pub struct Environment {
+    values: HashMap<String, Object>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
 }
 
-impl EnvironmentStack {
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Environment {
     pub fn new() -> Self {
-        EnvironmentStack {
-            environments: vec![HashMap::new()],
+        Environment {
+            values: HashMap::new(),
+            enclosing: None,
         }
     }
 
-    pub fn current_environment(&mut self) -> &mut HashMap<String, Object> {
-        self.environments.last_mut().unwrap()
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Self {
+        Environment {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
     }
 
-    pub fn push_environment(&mut self) {
-        self.environments.push(HashMap::new());
+    pub fn values(&self) -> &HashMap<String, Object> {
+        &self.values
     }
 
-    pub fn pop_environment(&mut self) {
-        if self.environments.len() > 1 {
-            self.environments.pop();
-        }
+    /// Swaps in a whole new set of bindings, returning the old ones. Closures
+    /// holding this scope see the new bindings.
+    pub fn replace_values(&mut self, values: HashMap<String, Object>) -> HashMap<String, Object> {
+        std::mem::replace(&mut self.values, values)
     }
 
     pub fn define(&mut self, name: &Token, value: Object) {
-        self.current_environment()
-            .insert(name.lexeme.clone(), value);
+        self.values.insert(name.lexeme.clone(), value);
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Object) {
+        self.values.insert(name.to_owned(), value);
     }
 
     pub fn get(&self, name: &Token) -> Result<Object, RuntimeError> {
-        // Search through the stack from top to bottom (most recent to oldest)
-        for environment in self.environments.iter().rev() {
-            if let Some(value) = environment.get(&name.lexeme) {
-                return Ok(value.clone());
-            }
+        // Search outwards from the innermost scope
+        if let Some(value) = self.values.get(&name.lexeme) {
+            return Ok(value.clone());
+        }
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow().get(name);
         }
 
-        Err(RuntimeError {
-            message: format!("Undefined variable '{}'.", name.lexeme),
-            token: name.clone(),
-        })
+        Err(undefined(name))
     }
 
-    pub fn define_global(&mut self, name: &str, value: Object) {
-        self.environments
-            .first_mut()
-            .unwrap()
-            .insert(name.to_owned(), value);
+    /// Reads a variable the resolver found `distance` scopes out.
+    pub fn get_at(&self, distance: usize, name: &Token) -> Result<Object, RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) => self.get_local(name),
+            (_, Some(enclosing)) => enclosing.borrow().get_at(distance - 1, name),
+            (_, None) => Err(undefined(name)),
+        }
+    }
+
+    /// Assigns to a variable the resolver found `distance` scopes out.
+    pub fn assign_at(
+        &mut self,
+        distance: usize,
+        name: &Token,
+        value: Object,
+    ) -> Result<(), RuntimeError> {
+        match (distance, &self.enclosing) {
+            (0, _) => match self.values.get_mut(&name.lexeme) {
+                Some(slot) => {
+                    *slot = value;
+                    Ok(())
+                }
+                None => Err(undefined(name)),
+            },
+            (_, Some(enclosing)) => enclosing.borrow_mut().assign_at(distance - 1, name, value),
+            (_, None) => Err(undefined(name)),
+        }
+    }
+
+    fn get_local(&self, name: &Token) -> Result<Object, RuntimeError> {
+        self.values
+            .get(&name.lexeme)
+            .cloned()
+            .ok_or_else(|| undefined(name))
     }
 
     pub fn assign(&mut self, name: &Token, value: Object) -> Result<(), RuntimeError> {
-        // Search through the stack from top to bottom (most recent to oldest)
-        for environment in self.environments.iter_mut().rev() {
-            if environment.contains_key(&name.lexeme) {
-                environment.insert(name.lexeme.clone(), value);
-                return Ok(());
-            }
+        // Search outwards from the innermost scope
+        if let Some(slot) = self.values.get_mut(&name.lexeme) {
+            *slot = value;
+            return Ok(());
+        }
+        if let Some(enclosing) = &self.enclosing {
+            return enclosing.borrow_mut().assign(name, value);
         }
 
         Err(RuntimeError {
-            message: format!("Undefined variable '{}'.", name.lexeme),
+            message: format!(
+                "Undefined variable '{}'. Did you mean 'var {} = ...'?",
+                name.lexeme, name.lexeme
+            ),
             token: name.clone(),
         })
     }
 }
+
+fn undefined(name: &Token) -> RuntimeError {
+    RuntimeError {
+        message: format!("Undefined variable '{}'.", name.lexeme),
+        token: name.clone(),
+    }
+}
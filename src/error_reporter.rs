@@ -1,38 +1,208 @@
 use crate::interpreter::RuntimeError;
 use crate::tokens::{Token, TokenType};
 
+/// Which pass produced a diagnostic. Resolver errors are reported at a token
+/// just like parse errors, so they share the `Parse` kind. Warnings don't stop
+/// the program from running.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiagnosticKind {
+    Lexical,
+    Parse,
+    Runtime,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: u32,
+    pub column: Option<u32>,
+    pub message: String,
+    pub kind: DiagnosticKind,
+}
+
 pub struct ErrorReporter {
-    pub had_error: bool,
-    pub had_runtime_error: bool,
+    errors: Vec<Diagnostic>,
+    // Quoted under errors that know their column
+    source: Option<String>,
+}
+
+impl Default for ErrorReporter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ErrorReporter {
     pub fn new() -> Self {
         ErrorReporter {
-            had_error: false,
-            had_runtime_error: false,
+            errors: Vec::new(),
+            source: None,
+        }
+    }
+
+    pub fn with_source(source: &str) -> Self {
+        ErrorReporter {
+            errors: Vec::new(),
+            source: Some(source.to_string()),
         }
     }
 
+    /// Every diagnostic reported so far, in the order they were reported.
+    pub fn errors(&self) -> &[Diagnostic] {
+        &self.errors
+    }
+
+    pub fn had_error(&self) -> bool {
+        self.errors.iter().any(|error| {
+            !matches!(
+                error.kind,
+                DiagnosticKind::Runtime | DiagnosticKind::Warning
+            )
+        })
+    }
+
+    pub fn had_runtime_error(&self) -> bool {
+        self.errors
+            .iter()
+            .any(|error| error.kind == DiagnosticKind::Runtime)
+    }
+
+    /// Forgets everything reported so far, e.g. between lines of the REPL.
+    pub fn clear(&mut self) {
+        self.errors.clear();
+    }
+
     pub fn error(&mut self, line: u32, message: &str) {
-        self.report(line, "", message);
+        self.report(line, None, "", message, DiagnosticKind::Lexical);
     }
 
     pub fn runtime_error(&mut self, error: RuntimeError) {
         eprintln!("{} \n[line {}]", error.message, error.token.line);
-        self.had_runtime_error = true;
+        self.errors.push(Diagnostic {
+            line: error.token.line,
+            column: None,
+            message: error.message,
+            kind: DiagnosticKind::Runtime,
+        });
     }
 
     pub fn error_at_token(&mut self, token: &Token, message: &str) {
         if token.token_type == TokenType::Eof {
-            self.report(token.line, " at end", message);
+            self.report(
+                token.line,
+                token.column,
+                " at end",
+                message,
+                DiagnosticKind::Parse,
+            );
         } else {
-            self.report(token.line, &format!(" at '{}'", token.lexeme), message);
+            self.report(
+                token.line,
+                token.column,
+                &format!(" at '{}'", token.escaped_lexeme()),
+                message,
+                DiagnosticKind::Parse,
+            );
         }
     }
 
-    fn report(&mut self, line: u32, loc: &str, message: &str) {
-        eprintln!("[line {}] Error {}: {}", line, loc, message);
-        self.had_error = true;
+    pub fn warning_at_token(&mut self, token: &Token, message: &str) {
+        let loc = format!(" at '{}'", token.escaped_lexeme());
+        eprintln!(
+            "{}",
+            self.format_diagnostic("Warning", token.line, token.column, &loc, message)
+        );
+        self.errors.push(Diagnostic {
+            line: token.line,
+            column: token.column,
+            message: message.to_string(),
+            kind: DiagnosticKind::Warning,
+        });
+    }
+
+    fn report(
+        &mut self,
+        line: u32,
+        column: Option<u32>,
+        loc: &str,
+        message: &str,
+        kind: DiagnosticKind,
+    ) {
+        eprintln!("{}", self.format_report(line, column, loc, message));
+        self.errors.push(Diagnostic {
+            line,
+            column,
+            message: message.to_string(),
+            kind,
+        });
+    }
+
+    /// The error message, followed by the offending source line with a caret
+    /// under the column when both are known.
+    fn format_report(&self, line: u32, column: Option<u32>, loc: &str, message: &str) -> String {
+        self.format_diagnostic("Error", line, column, loc, message)
+    }
+
+    fn format_diagnostic(
+        &self,
+        label: &str,
+        line: u32,
+        column: Option<u32>,
+        loc: &str,
+        message: &str,
+    ) -> String {
+        let mut text = format!("[line {}] {} {}: {}", line, label, loc, message);
+        if let Some(context) = column.and_then(|column| self.source_context(line, column)) {
+            text.push('\n');
+            text.push_str(&context);
+        }
+        text
+    }
+
+    fn source_context(&self, line: u32, column: u32) -> Option<String> {
+        let source_line = self.source.as_ref()?.lines().nth(line as usize - 1)?;
+        // Keep tabs so the caret lines up however wide the terminal draws them
+        let padding: String = source_line
+            .chars()
+            .take(column as usize - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        Some(format!("    {}\n    {}^", source_line, padding))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    #[test]
+    fn test_report_points_at_column() {
+        let source = "var a = 1;\nprint a +;\n";
+        let mut parser =
+            Parser::new(Scanner::new(source.to_string()).scan_tokens()).with_source(source);
+        parser.parse();
+
+        let error = &parser.error_reporter.errors()[0];
+        assert_eq!((error.line, error.column), (2, Some(10)));
+        assert_eq!(
+            parser.error_reporter.format_report(
+                error.line,
+                error.column,
+                " at ';'",
+                &error.message
+            ),
+            "[line 2] Error  at ';': Expect expression.\n    print a +;\n             ^"
+        );
+    }
+
+    #[test]
+    fn test_report_without_source_has_no_context() {
+        let reporter = ErrorReporter::new();
+        assert_eq!(
+            reporter.format_report(1, Some(3), "", "Oops."),
+            "[line 1] Error : Oops."
+        );
     }
 }
@@ -1,16 +1,52 @@
 use crate::tokens::{Object, Token};
+use std::cell::Cell;
 use std::fmt;
 
+/// How many scopes separate a variable reference from its declaration. The
+/// resolver fills this in; references it leaves unset are globals.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Depth(Cell<Option<usize>>);
+
+impl Depth {
+    pub fn get(&self) -> Option<usize> {
+        self.0.get()
+    }
+
+    pub fn set(&self, depth: usize) {
+        self.0.set(Some(depth));
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Unary(Token, Box<Expr>),
     Binary(Box<Expr>, Token, Box<Expr>),
     Call(Box<Expr>, Token, Vec<Expr>),
+    /// `object.name op= value`, holding the binary operator (`+` for `+=`).
+    /// Unlike a desugared `Set`, the object is only evaluated once.
+    CompoundSet(Box<Expr>, Token, Token, Box<Expr>),
+    /// `list[index] op= value`, laid out like `IndexSet` with the binary
+    /// operator before the value. The list and index are evaluated once.
+    CompoundIndexSet(Box<Expr>, Token, Box<Expr>, Token, Box<Expr>),
+    Get(Box<Expr>, Token),
     Grouping(Box<Expr>),
+    /// The indexed value, the `]` for error locations, and the index.
+    Index(Box<Expr>, Token, Box<Expr>),
+    /// `list[index] = value`, laid out like `Index` with the value last.
+    IndexSet(Box<Expr>, Token, Box<Expr>, Box<Expr>),
+    List(Vec<Expr>),
     Literal(Object),
-    Variable(Token),
-    Assignment(Token, Box<Expr>),
+    Variable(Token, Depth),
+    Assignment(Token, Box<Expr>, Depth),
+    /// `a, b = value`, assigning each variable an element of a list of
+    /// exactly that many values.
+    Unpack(Vec<(Token, Depth)>, Box<Expr>),
+    Set(Box<Expr>, Token, Box<Expr>),
+    /// The `super` keyword and the method name that follows it.
+    Super(Token, Token),
+    This(Token),
     Logical(Box<Expr>, Token, Box<Expr>),
+    Print(Box<Expr>),
 }
 
 impl fmt::Display for Expr {
@@ -19,19 +55,55 @@ impl fmt::Display for Expr {
             Expr::Binary(left, operator, right) => {
                 write!(f, "{}", parenthesize(&operator.lexeme, &[left, right]))
             }
+            Expr::CompoundSet(object, name, operator, value) => write!(
+                f,
+                "{}.{} {}= {}",
+                object, name.lexeme, operator.lexeme, value
+            ),
+            Expr::CompoundIndexSet(object, _, index, operator, value) => write!(
+                f,
+                "{}",
+                parenthesize(
+                    &format!("index{}=", operator.lexeme),
+                    &[object, index, value]
+                )
+            ),
             Expr::Grouping(expression) => write!(f, "{}", parenthesize("group", &[expression])),
-            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Index(object, _, index) => {
+                write!(f, "{}", parenthesize("index", &[object, index]))
+            }
+            Expr::IndexSet(object, _, index, value) => {
+                write!(f, "{}", parenthesize("index-set", &[object, index, value]))
+            }
+            Expr::List(items) => {
+                let refs: Vec<&Expr> = items.iter().collect();
+                write!(f, "{}", parenthesize("list", &refs))
+            }
+            Expr::Literal(value) => write!(f, "{}", value.repr()),
             Expr::Unary(operator, right) => {
                 write!(f, "{}", parenthesize(&operator.lexeme, &[right]))
             }
-            Expr::Variable(token) => write!(f, "{}", token.lexeme),
-            Expr::Assignment(token, expr) => write!(f, "{} = {}", &token.lexeme, expr),
+            Expr::Variable(token, _) => write!(f, "{}", token.lexeme),
+            Expr::Assignment(token, expr, _) => write!(f, "{} = {}", &token.lexeme, expr),
+            Expr::Unpack(targets, value) => {
+                let names: Vec<&str> = targets
+                    .iter()
+                    .map(|(name, _)| name.lexeme.as_str())
+                    .collect();
+                write!(f, "{} = {}", names.join(", "), value)
+            }
             Expr::Logical(left, operator, right) => {
                 write!(f, "{}", parenthesize(&operator.lexeme, &[left, right]))
             }
-            Expr::Call(callee, paren, args) => {
-                let refs: Vec<&Expr> = args.iter().collect();
-                write!(f, "{}{}{}", callee, paren, parenthesize("call", &refs))
+            Expr::Get(object, name) => write!(f, "{}.{}", object, name.lexeme),
+            Expr::Set(object, name, value) => write!(f, "{}.{} = {}", object, name.lexeme, value),
+            Expr::Super(_, method) => write!(f, "super.{}", method.lexeme),
+            Expr::This(_) => write!(f, "this"),
+            Expr::Print(expr) => write!(f, "{}", parenthesize("print", &[expr])),
+            Expr::Call(callee, _, args) => {
+                let mut refs: Vec<&Expr> = vec![callee];
+                refs.extend(args);
+                write!(f, "{}", parenthesize("call", &refs))
             }
         }
     }
@@ -1,77 +1,468 @@
-use crate::callable::{Callable, NativeCallable};
-use crate::environment::EnvironmentStack;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::callable::{Callable, LoxFunction};
+use crate::class::{LoxClass, LoxInstance};
+use crate::environment::Environment;
 use crate::error_reporter::ErrorReporter;
-use crate::expressions::Expr;
-use crate::statements::Stmt;
+use crate::expressions::{Depth, Expr};
+use crate::natives;
+use crate::statements::{PrintFormat, Stmt};
 use crate::tokens::{Object, Token, TokenType};
 
+const PRELUDE: &str = include_str!("prelude.lox");
+
 #[derive(Debug)]
 pub struct RuntimeError {
     pub message: String,
     pub token: Token,
 }
 
+/// Signals that unwind out of statement execution.
+#[derive(Debug)]
+pub enum Unwind {
+    Error(RuntimeError),
+    Exit(i32),
+    Return(Object),
+    /// Carries the keyword, to report a `break` that ends up outside any loop.
+    Break(Token),
+    Continue(Token),
+}
+
+impl Unwind {
+    /// The error for a `break` or `continue` that escaped every loop. The
+    /// parser rejects these, but a host may still interpret the statements.
+    pub(crate) fn outside_loop(keyword: Token) -> RuntimeError {
+        RuntimeError {
+            message: format!("Can't use '{}' outside of a loop.", keyword.lexeme),
+            token: keyword,
+        }
+    }
+}
+
+impl From<RuntimeError> for Unwind {
+    fn from(error: RuntimeError) -> Self {
+        Unwind::Error(error)
+    }
+}
+
+/// The global bindings at some point in a session, for `Interpreter::restore`.
+/// Values are cloned shallowly, so instances are shared with the live session.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    globals: HashMap<String, Object>,
+}
+
+/// Which values count as false in conditions and logical operators.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Truthiness {
+    /// Only `nil` and `false` are falsy.
+    #[default]
+    Lox,
+    /// `0`, `NaN` and the empty string are falsy as well.
+    JsLike,
+}
+
 pub struct Interpreter {
     pub error_reporter: ErrorReporter,
-    environment: EnvironmentStack,
+    /// When false, `assert` statements are skipped without evaluating their condition.
+    pub assertions_enabled: bool,
+    /// When true, output is flushed after every `print` so it shows up before
+    /// the program does anything else, such as waiting for input.
+    pub auto_flush: bool,
+    /// When true, a `nil` operand of `+`, `-` or `*` stands in for that
+    /// operator's identity (0, 0 and 1), so `nil + 1` is `1` rather than an error.
+    pub nil_arithmetic: bool,
+    /// How deeply calls may nest before a "Stack overflow." runtime error.
+    /// The default fits comfortably in an 8 MiB main thread even in a debug
+    /// build; running out of native stack instead would abort the process.
+    pub max_call_depth: usize,
+    truthiness: Truthiness,
+    // Where `print` and diagnostics from natives write to
+    out: Box<dyn Write>,
+    err: Box<dyn Write>,
+    // Where `input` reads lines from. Unset means stdin, which is locked
+    // afresh for each line so the REPL can still read from it in between.
+    input: Option<Box<dyn BufRead>>,
+    // Time since the Unix epoch, replaceable so tests can fix the time
+    clock: fn() -> Duration,
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // Number of calls currently in progress, and the most there have ever been
+    call_depth: usize,
+    max_depth_reached: usize,
+    // Statements deferred by each call in progress, with the scope to run them in
+    deferred: Vec<Vec<(Stmt, Rc<RefCell<Environment>>)>>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
+    /// An interpreter with the natives and the Lox prelude already defined.
     pub fn new() -> Self {
-        let mut env = EnvironmentStack::new();
-        let clock = NativeCallable::new(0, |_, _| {
-            Ok(Object::Number(
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_millis() as f64,
-            ))
-        });
-        env.define_global("clock", Object::NativeFunction(clock));
+        let mut interpreter = Self::without_prelude();
+        crate::run(PRELUDE, &mut interpreter).expect("The prelude has errors");
+        interpreter
+    }
+
+    /// Like `new`, but only the natives are defined, for minimal environments
+    /// that don't want the prelude's globals or its startup cost.
+    pub fn without_prelude() -> Self {
+        let mut globals = Environment::new();
+        natives::define_natives(&mut globals);
+        let globals = Rc::new(RefCell::new(globals));
         Self {
             error_reporter: ErrorReporter::new(),
-            environment: env,
+            assertions_enabled: true,
+            auto_flush: true,
+            nil_arithmetic: false,
+            max_call_depth: 400,
+            truthiness: Truthiness::default(),
+            out: Box::new(io::stdout()),
+            err: Box::new(io::stderr()),
+            input: None,
+            clock: || SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
+            environment: Rc::clone(&globals),
+            globals,
+            call_depth: 0,
+            max_depth_reached: 0,
+            deferred: Vec::new(),
+        }
+    }
+
+    pub fn with_output(mut self, out: Box<dyn Write>) -> Self {
+        self.out = out;
+        self
+    }
+
+    pub fn with_error_output(mut self, err: Box<dyn Write>) -> Self {
+        self.err = err;
+        self
+    }
+
+    pub fn with_input(mut self, input: Box<dyn BufRead>) -> Self {
+        self.input = Some(input);
+        self
+    }
+
+    pub fn with_clock(mut self, clock: fn() -> Duration) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_truthiness(mut self, truthiness: Truthiness) -> Self {
+        self.truthiness = truthiness;
+        self
+    }
+
+    /// Runs the statements, returning the exit code if the script called `exit`.
+    /// A runtime error only abandons the top-level statement it happened in.
+    /// It is recorded on the error reporter and the next statement runs.
+    pub fn interpret(&mut self, statements: Vec<Stmt>) -> Option<i32> {
+        for statement in statements {
+            match self.execute(&statement) {
+                Ok(()) => {}
+                Err(Unwind::Error(err)) => self.error_reporter.runtime_error(err),
+                Err(Unwind::Exit(code)) => return Some(code),
+                Err(Unwind::Return(_)) => {
+                    unreachable!("Return outside of a function is a runtime error")
+                }
+                Err(Unwind::Break(keyword) | Unwind::Continue(keyword)) => self
+                    .error_reporter
+                    .runtime_error(Unwind::outside_loop(keyword)),
+            }
         }
+        None
     }
 
-    pub fn interpret(&mut self, statements: Vec<Stmt>) {
+    /// Like `interpret`, but hands back the value of each top-level expression
+    /// statement and stops at the first runtime error instead of reporting it.
+    /// An `exit` ends the run early with the values collected so far, along
+    /// with its exit code.
+    pub fn interpret_collect(
+        &mut self,
+        statements: Vec<Stmt>,
+    ) -> Result<(Vec<Object>, Option<i32>), RuntimeError> {
+        let mut values = Vec::new();
         for statement in statements {
-            if let Err(err) = self.execute(&statement) {
-                self.error_reporter.runtime_error(err);
+            let result = match &statement {
+                Stmt::Expression(expr) => self.evaluate(expr).map(|value| values.push(value)),
+                statement => self.execute(statement),
+            };
+            match result {
+                Ok(()) => {}
+                Err(Unwind::Error(err)) => return Err(err),
+                Err(Unwind::Exit(code)) => return Ok((values, Some(code))),
+                Err(Unwind::Break(keyword) | Unwind::Continue(keyword)) => {
+                    return Err(Unwind::outside_loop(keyword));
+                }
+                Err(Unwind::Return(_)) => {
+                    unreachable!("Return outside of a function is a runtime error")
+                }
             }
         }
+        Ok((values, None))
+    }
+
+    /// The deepest nesting of calls seen so far.
+    pub fn max_depth_reached(&self) -> usize {
+        self.max_depth_reached
+    }
+
+    pub fn globals(&self) -> HashMap<String, Object> {
+        self.globals.borrow().values().clone()
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            globals: self.globals(),
+        }
+    }
+
+    /// Puts the globals back as they were when `snapshot` was taken, including
+    /// any natives that have since been reassigned.
+    pub fn restore(&mut self, snapshot: Snapshot) {
+        self.globals.borrow_mut().replace_values(snapshot.globals);
     }
 
-    fn execute(&mut self, stmt: &Stmt) -> Result<(), RuntimeError> {
+    fn execute(&mut self, stmt: &Stmt) -> Result<(), Unwind> {
         match stmt {
             // these map the "visit<type>Stmt" functions from the book
-            Stmt::Print(expr) => self.execute_print_statement(expr),
+            Stmt::Assert(keyword, condition) => self.execute_assert_statement(keyword, condition),
+            Stmt::Print(expr, format) => self.execute_print_statement(expr, *format),
             Stmt::Expression(expr) => self.execute_expression_statement(expr),
+            Stmt::Exit(keyword, code) => self.execute_exit_statement(keyword, code),
+            Stmt::Function(name, params, body) => {
+                self.execute_function_statement(name, params, body)
+            }
+            Stmt::Return(keyword, value) => self.execute_return_statement(keyword, value),
             Stmt::Var(name, initializer) => self.execute_var_statement(name, initializer),
+            Stmt::Destructure(names, value) => self.execute_destructure_statement(names, value),
             Stmt::Block(statements) => self.execute_block_statement(statements),
+            Stmt::Break(keyword) => Err(Unwind::Break(keyword.clone())),
+            Stmt::Class(name, superclass, methods) => {
+                self.execute_class_statement(name, superclass, methods)
+            }
+            Stmt::Continue(keyword) => Err(Unwind::Continue(keyword.clone())),
+            Stmt::Defer(keyword, statement) => self.execute_defer_statement(keyword, statement),
             Stmt::If(condition, then_branch, else_branch) => {
                 self.execute_if_statement(condition, then_branch, else_branch)
             }
-            Stmt::While(condition, body) => self.execute_while_statement(condition, body),
+            Stmt::For(initializer, condition, increment, body) => {
+                self.execute_for_statement(initializer, condition, increment, body)
+            }
+            Stmt::While(condition, body, else_branch) => {
+                self.execute_while_statement(condition, body, else_branch)
+            }
         }
     }
 
     // visitExpressionStmt
-    fn execute_expression_statement(&mut self, expr: &Expr) -> Result<(), RuntimeError> {
+    fn execute_expression_statement(&mut self, expr: &Expr) -> Result<(), Unwind> {
         self.evaluate(expr)?;
         Ok(())
     }
 
+    fn execute_assert_statement(
+        &mut self,
+        keyword: &Token,
+        condition: &Expr,
+    ) -> Result<(), Unwind> {
+        if !self.assertions_enabled {
+            return Ok(());
+        }
+        let value = self.evaluate(condition)?;
+        if !self.is_truthy(&value) {
+            Err(RuntimeError {
+                message: "Assertion failed.".to_string(),
+                token: keyword.clone(),
+            })?
+        }
+        Ok(())
+    }
+
+    // visitFunctionStmt
+    fn execute_function_statement(
+        &mut self,
+        name: &Token,
+        params: &[Token],
+        body: &[Stmt],
+    ) -> Result<(), Unwind> {
+        let function = LoxFunction::new(
+            name.clone(),
+            params.into(),
+            body.into(),
+            Rc::clone(&self.environment),
+            false,
+        );
+        self.environment
+            .borrow_mut()
+            .define(name, Object::Callable(Rc::new(function)));
+        Ok(())
+    }
+
+    // visitClassStmt
+    fn execute_class_statement(
+        &mut self,
+        name: &Token,
+        superclass: &Option<Box<Expr>>,
+        methods: &[Stmt],
+    ) -> Result<(), Unwind> {
+        let superclass = match superclass.as_deref() {
+            Some(Expr::Variable(superclass, depth)) => {
+                match self.evaluate_variable_expr(superclass, depth)? {
+                    Object::Class(class) => Some(class),
+                    _ => Err(RuntimeError {
+                        message: "Superclass must be a class.".to_string(),
+                        token: superclass.clone(),
+                    })?,
+                }
+            }
+            Some(_) => unreachable!("The parser only produces variable superclasses"),
+            None => None,
+        };
+
+        // Methods of a subclass close over a scope where `super` is defined
+        let closure = match &superclass {
+            Some(superclass) => {
+                let mut environment = Environment::with_enclosing(Rc::clone(&self.environment));
+                let keyword = Token::new(TokenType::Super, "super".to_string(), None, name.line);
+                environment.define(&keyword, Object::Class(Rc::clone(superclass)));
+                Rc::new(RefCell::new(environment))
+            }
+            None => Rc::clone(&self.environment),
+        };
+
+        let mut class_methods = HashMap::new();
+        for method in methods {
+            if let Stmt::Function(method_name, params, body) = method {
+                let function = LoxFunction::new(
+                    method_name.clone(),
+                    params.as_slice().into(),
+                    body.as_slice().into(),
+                    Rc::clone(&closure),
+                    method_name.lexeme == "init",
+                );
+                class_methods.insert(method_name.lexeme.clone(), function);
+            }
+        }
+
+        let class = LoxClass::new(name.lexeme.clone(), superclass, class_methods);
+        self.environment
+            .borrow_mut()
+            .define(name, Object::Class(Rc::new(class)));
+        Ok(())
+    }
+
+    fn execute_defer_statement(&mut self, keyword: &Token, statement: &Stmt) -> Result<(), Unwind> {
+        // The parser reports this too, but the statement is still produced
+        let Some(frame) = self.deferred.last_mut() else {
+            Err(RuntimeError {
+                message: "Can't use 'defer' outside of a function.".to_string(),
+                token: keyword.clone(),
+            })?
+        };
+        frame.push((statement.clone(), Rc::clone(&self.environment)));
+        Ok(())
+    }
+
+    /// Runs a function body, then everything it deferred in reverse order.
+    /// Deferred statements still run when the body returns early or fails,
+    /// but not when the script exits.
+    pub fn execute_function_body(
+        &mut self,
+        body: &[Stmt],
+        environment: Environment,
+    ) -> Result<(), Unwind> {
+        self.deferred.push(Vec::new());
+        let result = self.execute_block(body, environment);
+        // The frame stays in place while deferred statements run, as they're
+        // still part of the call
+        let frame = std::mem::take(self.deferred.last_mut().expect("Pushed above"));
+        if matches!(result, Err(Unwind::Exit(_))) {
+            self.deferred.pop();
+            return result;
+        }
+
+        let mut deferred_result = Ok(());
+        for (statement, environment) in frame.into_iter().rev() {
+            let previous = std::mem::replace(&mut self.environment, environment);
+            let outcome = self.execute(&statement);
+            self.environment = previous;
+            if deferred_result.is_ok() {
+                deferred_result = outcome;
+            }
+        }
+        self.deferred.pop();
+
+        // An error from the body takes precedence over anything deferred
+        match (result, deferred_result) {
+            (Err(Unwind::Error(err)), _) => Err(Unwind::Error(err)),
+            (_, Err(unwind)) => Err(unwind),
+            (result, Ok(())) => result,
+        }
+    }
+
+    // visitReturnStmt
+    fn execute_return_statement(
+        &mut self,
+        keyword: &Token,
+        value: &Option<Box<Expr>>,
+    ) -> Result<(), Unwind> {
+        // The parser reports this too, but the statement is still produced
+        if self.deferred.is_empty() {
+            Err(RuntimeError {
+                message: "Can't return from top-level code.".to_string(),
+                token: keyword.clone(),
+            })?
+        }
+        let value = match value.as_ref() {
+            Some(value) => self.evaluate(value)?,
+            None => Object::Nil,
+        };
+        Err(Unwind::Return(value))
+    }
+
+    fn execute_exit_statement(
+        &mut self,
+        keyword: &Token,
+        code: &Option<Box<Expr>>,
+    ) -> Result<(), Unwind> {
+        let code = match code.as_ref() {
+            // Processes only report the low 8 bits of their status, so anything
+            // outside 0 to 255 would be silently mangled
+            Some(code) => match self.evaluate(code)? {
+                Object::Number(n) if n.fract() == 0.0 && (0.0..=255.0).contains(&n) => n as i32,
+                _ => Err(RuntimeError {
+                    message: "Exit code must be an integer from 0 to 255.".to_string(),
+                    token: keyword.clone(),
+                })?,
+            },
+            None => 0,
+        };
+        Err(Unwind::Exit(code))
+    }
+
     // visitPrintStmt
-    fn execute_print_statement(&mut self, expr: &Expr) -> Result<(), RuntimeError> {
+    fn execute_print_statement(&mut self, expr: &Expr, format: PrintFormat) -> Result<(), Unwind> {
         let value = self.evaluate(expr)?;
-        println!("{}", value);
+        let text = self.format_value(&value, format)?;
+        self.write_line(&text);
         Ok(())
     }
 
     //visitBlockStmt
-    fn execute_block_statement(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
-        self.execute_block(statements)
+    fn execute_block_statement(&mut self, statements: &[Stmt]) -> Result<(), Unwind> {
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        self.execute_block(statements, environment)
     }
 
     //visitIfStmt
@@ -80,7 +471,7 @@ impl Interpreter {
         condition: &Expr,
         then_branch: &Stmt,
         else_branch: &Option<Box<Stmt>>,
-    ) -> Result<(), RuntimeError> {
+    ) -> Result<(), Unwind> {
         let condition_value = self.evaluate(condition)?;
         if self.is_truthy(&condition_value) {
             self.execute(then_branch)?;
@@ -90,20 +481,19 @@ impl Interpreter {
         Ok(())
     }
 
-    fn execute_block(&mut self, statements: &[Stmt]) -> Result<(), RuntimeError> {
-        // Create a new environment for the block
-        self.environment.push_environment();
-
-        for statement in statements {
-            if let Err(e) = self.execute(statement) {
-                self.environment.pop_environment();
-                return Err(e);
-            }
-        }
-
-        self.environment.pop_environment();
-
-        Ok(())
+    /// Runs the statements with `environment` as the innermost scope, restoring
+    /// the current scope afterwards even if execution unwinds.
+    pub fn execute_block(
+        &mut self,
+        statements: &[Stmt],
+        environment: Environment,
+    ) -> Result<(), Unwind> {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = statements
+            .iter()
+            .try_for_each(|statement| self.execute(statement));
+        self.environment = previous;
+        result
     }
 
     // visitVarStmt
@@ -111,13 +501,26 @@ impl Interpreter {
         &mut self,
         name: &Token,
         initializer: &Option<Box<Expr>>,
-    ) -> Result<(), RuntimeError> {
+    ) -> Result<(), Unwind> {
         let value = if let Some(initializer) = initializer.as_ref() {
             self.evaluate(initializer)?
         } else {
             Object::Nil
         };
-        self.environment.define(name, value);
+        self.environment.borrow_mut().define(name, value);
+        Ok(())
+    }
+
+    fn execute_destructure_statement(
+        &mut self,
+        names: &[Token],
+        value: &Expr,
+    ) -> Result<(), Unwind> {
+        let value = self.evaluate(value)?;
+        let values = Self::unpack(&names[0], names.len(), &value)?;
+        for (name, value) in names.iter().zip(values) {
+            self.environment.borrow_mut().define(name, value);
+        }
         Ok(())
     }
 
@@ -126,28 +529,100 @@ impl Interpreter {
         &mut self,
         condition: &Expr,
         body: &Stmt,
-    ) -> Result<(), RuntimeError> {
+        else_branch: &Option<Box<Stmt>>,
+    ) -> Result<(), Unwind> {
         loop {
             let condition_val = self.evaluate(condition)?;
             if !self.is_truthy(&condition_val) {
                 break;
             }
-            self.execute(body)?;
+            match self.execute(body) {
+                Ok(()) | Err(Unwind::Continue(_)) => {}
+                // Breaking out skips the else branch
+                Err(Unwind::Break(_)) => return Ok(()),
+                Err(unwind) => return Err(unwind),
+            }
+        }
+        if let Some(else_branch) = else_branch.as_ref() {
+            self.execute(else_branch)?;
         }
         Ok(())
     }
 
-    fn evaluate(&mut self, expr: &Expr) -> Result<Object, RuntimeError> {
+    fn execute_for_statement(
+        &mut self,
+        initializer: &Option<Box<Stmt>>,
+        condition: &Option<Box<Expr>>,
+        increment: &Option<Box<Expr>>,
+        body: &Stmt,
+    ) -> Result<(), Unwind> {
+        // A variable declared in the initializer is scoped to the loop
+        let environment = Environment::with_enclosing(Rc::clone(&self.environment));
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+        let result = self.run_for_loop(initializer, condition, increment, body);
+        self.environment = previous;
+        result
+    }
+
+    fn run_for_loop(
+        &mut self,
+        initializer: &Option<Box<Stmt>>,
+        condition: &Option<Box<Expr>>,
+        increment: &Option<Box<Expr>>,
+        body: &Stmt,
+    ) -> Result<(), Unwind> {
+        if let Some(initializer) = initializer {
+            self.execute(initializer)?;
+        }
+        loop {
+            if let Some(condition) = condition {
+                let condition_val = self.evaluate(condition)?;
+                if !self.is_truthy(&condition_val) {
+                    return Ok(());
+                }
+            }
+            match self.execute(body) {
+                // The increment still runs after a continue
+                Ok(()) | Err(Unwind::Continue(_)) => {}
+                Err(Unwind::Break(_)) => return Ok(()),
+                Err(unwind) => return Err(unwind),
+            }
+            if let Some(increment) = increment {
+                self.evaluate(increment)?;
+            }
+        }
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> Result<Object, Unwind> {
         match expr {
             // These map the "visit<type>Expr" methods from the book
             Expr::Literal(literal) => self.evaluate_literal_expr(literal),
             Expr::Grouping(expr) => self.evaluate_grouping_expr(expr),
+            Expr::Index(object, bracket, index) => self.evaluate_index_expr(object, bracket, index),
+            Expr::IndexSet(object, bracket, index, value) => {
+                self.evaluate_index_set_expr(object, bracket, index, value)
+            }
+            Expr::List(items) => self.evaluate_list_expr(items),
             Expr::Unary(op, right) => self.evaluate_unary_expr(op, right),
             Expr::Binary(left, op, right) => self.evaluate_binary_expr(left, op, right),
-            Expr::Variable(name) => self.evaluate_variable_expr(name),
-            Expr::Assignment(name, value) => self.evaluate_assignment_expr(name, value),
+            Expr::Variable(name, depth) => self.evaluate_variable_expr(name, depth),
+            Expr::Assignment(name, value, depth) => {
+                self.evaluate_assignment_expr(name, value, depth)
+            }
+            Expr::Unpack(targets, value) => self.evaluate_unpack_expr(targets, value),
             Expr::Logical(left, op, right) => self.evaluate_logical_expr(left, op, right),
             Expr::Call(callee, paren, args) => self.evaluate_call_expr(callee, paren, args),
+            Expr::CompoundSet(object, name, op, value) => {
+                self.evaluate_compound_set_expr(object, name, op, value)
+            }
+            Expr::CompoundIndexSet(object, bracket, index, op, value) => {
+                self.evaluate_compound_index_set_expr(object, bracket, index, op, value)
+            }
+            Expr::Get(object, name) => self.evaluate_get_expr(object, name),
+            Expr::Set(object, name, value) => self.evaluate_set_expr(object, name, value),
+            Expr::Super(keyword, method) => self.evaluate_super_expr(keyword, method),
+            Expr::This(keyword) => self.evaluate_this_expr(keyword),
+            Expr::Print(expr) => self.evaluate_print_expr(expr),
         }
     }
 
@@ -156,21 +631,93 @@ impl Interpreter {
         &mut self,
         name: &Token,
         value: &Expr,
-    ) -> Result<Object, RuntimeError> {
+        depth: &Depth,
+    ) -> Result<Object, Unwind> {
+        let value = self.evaluate(value)?;
+        self.assign_variable(name, depth, value.clone())?;
+        Ok(value)
+    }
+
+    fn assign_variable(
+        &mut self,
+        name: &Token,
+        depth: &Depth,
+        value: Object,
+    ) -> Result<(), RuntimeError> {
+        match depth.get() {
+            Some(distance) => self
+                .environment
+                .borrow_mut()
+                .assign_at(distance, name, value),
+            None => self.globals.borrow_mut().assign(name, value),
+        }
+    }
+
+    fn evaluate_unpack_expr(
+        &mut self,
+        targets: &[(Token, Depth)],
+        value: &Expr,
+    ) -> Result<Object, Unwind> {
         let value = self.evaluate(value)?;
-        self.environment.assign(name, value.clone())?;
+        let values = Self::unpack(&targets[0].0, targets.len(), &value)?;
+        for ((name, depth), value) in targets.iter().zip(values) {
+            self.assign_variable(name, depth, value)?;
+        }
         Ok(value)
     }
 
+    /// The elements of `value`, which must be a list of `count` of them.
+    /// Errors are reported at `first`, the first name being unpacked into.
+    fn unpack(first: &Token, count: usize, value: &Object) -> Result<Vec<Object>, RuntimeError> {
+        let values = match value {
+            Object::List(list) => list.borrow().clone(),
+            other => {
+                return Err(RuntimeError {
+                    message: format!("Can only unpack a list, got a {}.", other.type_name()),
+                    token: first.clone(),
+                });
+            }
+        };
+        if values.len() != count {
+            return Err(RuntimeError {
+                message: format!(
+                    "Expected {} values to unpack but got {}.",
+                    count,
+                    values.len()
+                ),
+                token: first.clone(),
+            });
+        }
+        Ok(values)
+    }
+
     //visitBinaryExpr
     fn evaluate_binary_expr(
         &mut self,
         left: &Expr,
         op: &Token,
         right: &Expr,
-    ) -> Result<Object, RuntimeError> {
+    ) -> Result<Object, Unwind> {
+        let chained = self.is_comparison(left);
         let left = self.evaluate(left)?;
         let right = self.evaluate(right)?;
+        self.binary_operation(op, left, right, chained)
+    }
+
+    /// Applies a binary operator to operands that have already been evaluated.
+    /// `chained` is set when the left operand was itself a comparison.
+    fn binary_operation(
+        &mut self,
+        op: &Token,
+        left: Object,
+        right: Object,
+        chained: bool,
+    ) -> Result<Object, Unwind> {
+        let (left, right) = if self.nil_arithmetic {
+            Self::replace_nil_operands(op, left, right)
+        } else {
+            (left, right)
+        };
         match op.token_type {
             TokenType::Minus => {
                 let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
@@ -178,44 +725,95 @@ impl Interpreter {
             }
             TokenType::Slash => {
                 let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
+                self.check_nonzero_divisor(op, right_num)?;
                 Ok(Object::Number(left_num / right_num))
             }
+            TokenType::Percent => {
+                let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
+                self.check_nonzero_divisor(op, right_num)?;
+                Ok(Object::Number(left_num % right_num))
+            }
             TokenType::Star => {
                 let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
                 Ok(Object::Number(left_num * right_num))
             }
-            TokenType::Plus => match (left, right) {
-                (Object::Number(left), Object::Number(right)) => Ok(Object::Number(left + right)),
-                (Object::String(left), Object::String(right)) => {
-                    Ok(Object::String(format!("{}{}", left, right)))
-                }
-                _ => Err(RuntimeError {
-                    message: "Operands must be two numbers or two strings".to_string(),
-                    token: op.clone(),
-                }),
-            },
+            TokenType::Plus => self.add_objects(left, right, op),
             TokenType::Greater => {
-                let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
-                Ok(Object::Boolean(left_num > right_num))
+                let ordering = self.compare_operands(op, &left, &right, chained)?;
+                Ok(Object::Boolean(matches!(ordering, Some(Ordering::Greater))))
             }
             TokenType::GreaterEqual => {
-                let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
-                Ok(Object::Boolean(left_num >= right_num))
+                let ordering = self.compare_operands(op, &left, &right, chained)?;
+                Ok(Object::Boolean(matches!(
+                    ordering,
+                    Some(Ordering::Greater | Ordering::Equal)
+                )))
             }
             TokenType::Less => {
-                let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
-                Ok(Object::Boolean(left_num < right_num))
+                let ordering = self.compare_operands(op, &left, &right, chained)?;
+                Ok(Object::Boolean(matches!(ordering, Some(Ordering::Less))))
             }
             TokenType::LessEqual => {
-                let (left_num, right_num) = self.check_number_operands(op, &left, &right)?;
-                Ok(Object::Boolean(left_num <= right_num))
+                let ordering = self.compare_operands(op, &left, &right, chained)?;
+                Ok(Object::Boolean(matches!(
+                    ordering,
+                    Some(Ordering::Less | Ordering::Equal)
+                )))
             }
             TokenType::BangEqual => Ok(Object::Boolean(!self.is_equal(&left, &right))),
             TokenType::EqualEqual => Ok(Object::Boolean(self.is_equal(&left, &right))),
             _ => Err(RuntimeError {
                 message: "Unhandled token type".to_string(),
                 token: op.clone(),
-            }),
+            }
+            .into()),
+        }
+    }
+
+    /// Swaps `nil` operands for the operator's identity. String concatenation
+    /// is left alone, as `nil` already has a display form there.
+    fn replace_nil_operands(op: &Token, left: Object, right: Object) -> (Object, Object) {
+        let identity = match op.token_type {
+            TokenType::Plus | TokenType::Minus => 0.0,
+            TokenType::Star => 1.0,
+            _ => return (left, right),
+        };
+        if matches!(left, Object::String(_)) || matches!(right, Object::String(_)) {
+            return (left, right);
+        }
+        let replace = |value: Object| match value {
+            Object::Nil => Object::Number(identity),
+            value => value,
+        };
+        (replace(left), replace(right))
+    }
+
+    /// All overloads of `+`, checked in order:
+    /// 1. number + number adds
+    /// 2. string + string concatenates
+    /// 3. string + any other value (either side) concatenates its display form
+    ///
+    /// Anything else is a runtime error.
+    fn add_objects(&mut self, left: Object, right: Object, op: &Token) -> Result<Object, Unwind> {
+        match (left, right) {
+            (Object::Number(left), Object::Number(right)) => Ok(Object::Number(left + right)),
+            (Object::String(left), Object::String(right)) => {
+                Ok(Object::String(format!("{}{}", left, right)))
+            }
+            (Object::String(left), right) => Ok(Object::String(format!(
+                "{}{}",
+                left,
+                self.stringify(&right)?
+            ))),
+            (left, Object::String(right)) => Ok(Object::String(format!(
+                "{}{}",
+                self.stringify(&left)?,
+                right
+            ))),
+            _ => Err(RuntimeError {
+                message: "Operands must be two numbers or two strings".to_string(),
+                token: op.clone(),
+            })?,
         }
     }
 
@@ -225,7 +823,7 @@ impl Interpreter {
         callee: &Expr,
         paren: &Token,
         args: &[Expr],
-    ) -> Result<Object, RuntimeError> {
+    ) -> Result<Object, Unwind> {
         let eval_callee = self.evaluate(callee)?;
         let arguments: Vec<Object> = args
             .iter()
@@ -233,14 +831,15 @@ impl Interpreter {
             .collect::<Result<Vec<_>, _>>()?;
 
         let callable = match eval_callee {
-            Object::NativeFunction(callable) => callable,
+            Object::Callable(callable) => callable,
+            Object::Class(class) => class,
             _ => Err(RuntimeError {
                 message: "Can only call functions and classes".to_string(),
                 token: paren.clone(),
             })?,
         };
 
-        if args.len() != callable.arity() {
+        if !callable.arity().accepts(args.len()) {
             Err(RuntimeError {
                 message: format!(
                     "Expected {} arguments but got {}",
@@ -251,66 +850,340 @@ impl Interpreter {
             })?
         }
 
-        callable.call(self, arguments)
-    }
-
-    // visitGroupingExpr
-    fn evaluate_grouping_expr(&mut self, expr: &Expr) -> Result<Object, RuntimeError> {
-        self.evaluate(expr)
-    }
-
-    // visitLiteralExpr
-    fn evaluate_literal_expr(&mut self, literal: &Object) -> Result<Object, RuntimeError> {
-        Ok(literal.clone())
+        self.call(callable, paren, arguments)
     }
 
-    // visitLogicalExpr
-    fn evaluate_logical_expr(
+    /// Calls `callable`, counting it towards `max_call_depth`.
+    pub(crate) fn call(
         &mut self,
-        left: &Expr,
-        op: &Token,
-        right: &Expr,
-    ) -> Result<Object, RuntimeError> {
-        let left_expr = self.evaluate(left)?;
-        if op.token_type == TokenType::Or {
-            if self.is_truthy(&left_expr) {
-                return Ok(left_expr);
-            }
-        } else if !self.is_truthy(&left_expr) {
-            return Ok(left_expr);
+        callable: Rc<dyn Callable>,
+        paren: &Token,
+        arguments: Vec<Object>,
+    ) -> Result<Object, Unwind> {
+        if self.call_depth >= self.max_call_depth {
+            Err(RuntimeError {
+                message: "Stack overflow.".to_string(),
+                token: paren.clone(),
+            })?
         }
-        self.evaluate(right)
+        self.call_depth += 1;
+        self.max_depth_reached = self.max_depth_reached.max(self.call_depth);
+        let result = callable.call(self, paren, arguments);
+        self.call_depth -= 1;
+        result
     }
 
-    // visitUnaryExpr
-    fn evaluate_unary_expr(
-        &mut self,
-        operator: &Token,
-        right: &Expr,
-    ) -> Result<Object, RuntimeError> {
-        let right = self.evaluate(right)?;
-        match operator.token_type {
-            TokenType::Minus => {
-                let right_num = self.check_number_operand(operator, &right)?;
-                Ok(Object::Number(-right_num))
-            }
-            TokenType::Bang => Ok(Object::Boolean(!self.is_truthy(&right))),
+    // visitGetExpr
+    fn evaluate_get_expr(&mut self, object: &Expr, name: &Token) -> Result<Object, Unwind> {
+        match self.evaluate(object)? {
+            Object::Instance(instance) => Ok(LoxInstance::get(&instance, name)?),
             _ => Err(RuntimeError {
-                message: "Invalid operator".to_string(),
-                token: operator.clone(),
-            }),
+                message: "Only instances have properties.".to_string(),
+                token: name.clone(),
+            })?,
         }
     }
 
-    // visitVariableExpr
-    fn evaluate_variable_expr(&mut self, name: &Token) -> Result<Object, RuntimeError> {
-        self.environment.get(name)
+    // visitSetExpr
+    fn evaluate_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        value: &Expr,
+    ) -> Result<Object, Unwind> {
+        let instance = match self.evaluate(object)? {
+            Object::Instance(instance) => instance,
+            _ => Err(RuntimeError {
+                message: "Only instances have fields.".to_string(),
+                token: name.clone(),
+            })?,
+        };
+        let value = self.evaluate(value)?;
+        instance.borrow_mut().set(name, value.clone());
+        Ok(value)
     }
 
-    fn check_number_operand(
-        &self,
-        operator: &Token,
-        operand: &Object,
+    // visitSuperExpr
+    fn evaluate_super_expr(&mut self, keyword: &Token, method: &Token) -> Result<Object, Unwind> {
+        let superclass = match self.environment.borrow().get(keyword)? {
+            Object::Class(class) => class,
+            _ => unreachable!("'super' is only ever bound to a class"),
+        };
+        let this = Token::new(TokenType::This, "this".to_string(), None, keyword.line);
+        let instance = match self.environment.borrow().get(&this)? {
+            Object::Instance(instance) => instance,
+            _ => unreachable!("'this' is only ever bound to an instance"),
+        };
+
+        match superclass.find_method(&method.lexeme) {
+            Some(function) => Ok(Object::Callable(Rc::new(function.bind(instance)))),
+            None => Err(RuntimeError {
+                message: format!("Undefined property '{}'.", method.lexeme),
+                token: method.clone(),
+            })?,
+        }
+    }
+
+    // visitThisExpr
+    fn evaluate_this_expr(&mut self, keyword: &Token) -> Result<Object, Unwind> {
+        Ok(self.environment.borrow().get(keyword)?)
+    }
+
+    // visitGroupingExpr
+    fn evaluate_grouping_expr(&mut self, expr: &Expr) -> Result<Object, Unwind> {
+        self.evaluate(expr)
+    }
+
+    fn evaluate_index_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+    ) -> Result<Object, Unwind> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        match object {
+            Object::List(list) => {
+                let list = list.borrow();
+                let i = self.list_index(bracket, &index, list.len())?;
+                Ok(list[i].clone())
+            }
+            _ => Err(RuntimeError {
+                message: "Only lists can be indexed.".to_string(),
+                token: bracket.clone(),
+            })?,
+        }
+    }
+
+    fn evaluate_index_set_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        value: &Expr,
+    ) -> Result<Object, Unwind> {
+        let object = self.evaluate(object)?;
+        let index = self.evaluate(index)?;
+        let value = self.evaluate(value)?;
+        match object {
+            Object::List(list) => {
+                let mut list = list.borrow_mut();
+                let i = self.list_index(bracket, &index, list.len())?;
+                list[i] = value.clone();
+                Ok(value)
+            }
+            _ => Err(RuntimeError {
+                message: "Only list elements can be assigned to.".to_string(),
+                token: bracket.clone(),
+            })?,
+        }
+    }
+
+    fn evaluate_compound_set_expr(
+        &mut self,
+        object: &Expr,
+        name: &Token,
+        op: &Token,
+        value: &Expr,
+    ) -> Result<Object, Unwind> {
+        let instance = match self.evaluate(object)? {
+            Object::Instance(instance) => instance,
+            _ => Err(RuntimeError {
+                message: "Only instances have fields.".to_string(),
+                token: name.clone(),
+            })?,
+        };
+        let current = LoxInstance::get(&instance, name)?;
+        let value = self.evaluate(value)?;
+        let value = self.binary_operation(op, current, value, false)?;
+        instance.borrow_mut().set(name, value.clone());
+        Ok(value)
+    }
+
+    fn evaluate_compound_index_set_expr(
+        &mut self,
+        object: &Expr,
+        bracket: &Token,
+        index: &Expr,
+        op: &Token,
+        value: &Expr,
+    ) -> Result<Object, Unwind> {
+        let list = match self.evaluate(object)? {
+            Object::List(list) => list,
+            _ => Err(RuntimeError {
+                message: "Only list elements can be assigned to.".to_string(),
+                token: bracket.clone(),
+            })?,
+        };
+        let index = self.evaluate(index)?;
+        let current = {
+            let list = list.borrow();
+            list[self.list_index(bracket, &index, list.len())?].clone()
+        };
+        let value = self.evaluate(value)?;
+        let value = self.binary_operation(op, current, value, false)?;
+        // The right-hand side may have changed the list's length
+        let mut list = list.borrow_mut();
+        let i = self.list_index(bracket, &index, list.len())?;
+        list[i] = value.clone();
+        Ok(value)
+    }
+
+    fn evaluate_list_expr(&mut self, items: &[Expr]) -> Result<Object, Unwind> {
+        let items = items
+            .iter()
+            .map(|item| self.evaluate(item))
+            .collect::<Result<Vec<Object>, Unwind>>()?;
+        Ok(Object::List(Rc::new(RefCell::new(items))))
+    }
+
+    // visitLiteralExpr
+    fn evaluate_literal_expr(&mut self, literal: &Object) -> Result<Object, Unwind> {
+        Ok(literal.clone())
+    }
+
+    // visitLogicalExpr
+    /// Returns the deciding operand itself rather than a boolean, so
+    /// `cond and a or b` works as a conditional as long as `a` is truthy.
+    fn evaluate_logical_expr(
+        &mut self,
+        left: &Expr,
+        op: &Token,
+        right: &Expr,
+    ) -> Result<Object, Unwind> {
+        let left_expr = self.evaluate(left)?;
+        if op.token_type == TokenType::Or {
+            if self.is_truthy(&left_expr) {
+                return Ok(left_expr);
+            }
+        } else if !self.is_truthy(&left_expr) {
+            return Ok(left_expr);
+        }
+        self.evaluate(right)
+    }
+
+    fn evaluate_print_expr(&mut self, expr: &Expr) -> Result<Object, Unwind> {
+        let value = self.evaluate(expr)?;
+        let text = self.stringify(&value)?;
+        self.write_line(&text);
+        Ok(value)
+    }
+
+    // visitUnaryExpr
+    fn evaluate_unary_expr(&mut self, operator: &Token, right: &Expr) -> Result<Object, Unwind> {
+        let right = self.evaluate(right)?;
+        match operator.token_type {
+            TokenType::Minus => {
+                let right_num = self.check_number_operand(operator, &right)?;
+                Ok(Object::Number(-right_num))
+            }
+            TokenType::Bang => Ok(Object::Boolean(!self.is_truthy(&right))),
+            _ => Err(RuntimeError {
+                message: "Invalid operator".to_string(),
+                token: operator.clone(),
+            }
+            .into()),
+        }
+    }
+
+    // visitVariableExpr
+    fn evaluate_variable_expr(&mut self, name: &Token, depth: &Depth) -> Result<Object, Unwind> {
+        match depth.get() {
+            Some(distance) => Ok(self.environment.borrow().get_at(distance, name)?),
+            None => Ok(self.globals.borrow().get(name)?),
+        }
+    }
+
+    pub(crate) fn write_line(&mut self, text: &str) {
+        // Matches println!, which also treats a failed write as fatal
+        writeln!(self.out, "{}", text).expect("Failed to write output");
+        if self.auto_flush {
+            self.flush_output();
+        }
+    }
+
+    /// Writes `text` without a newline and flushes it, so it shows up before
+    /// the program waits for input.
+    pub(crate) fn write_prompt(&mut self, text: &str) {
+        write!(self.out, "{}", text).expect("Failed to write output");
+        self.flush_output();
+    }
+
+    /// The next line of input without its line ending, or None at the end of input.
+    pub(crate) fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        let read = match &mut self.input {
+            Some(input) => input.read_line(&mut line)?,
+            None => io::stdin().read_line(&mut line)?,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    }
+
+    pub(crate) fn flush_output(&mut self) {
+        self.out.flush().expect("Failed to write output");
+    }
+
+    pub(crate) fn write_error_line(&mut self, text: &str) {
+        writeln!(self.err, "{}", text).expect("Failed to write output");
+    }
+
+    pub(crate) fn now(&self) -> Duration {
+        (self.clock)()
+    }
+
+    fn format_value(&mut self, value: &Object, format: PrintFormat) -> Result<String, Unwind> {
+        // Integers too large for an i64 print as usual rather than saturating
+        let n = match value {
+            Object::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => *n as i64,
+            _ => return self.stringify(value),
+        };
+        let sign = if n < 0 { "-" } else { "" };
+        Ok(match format {
+            PrintFormat::Default => value.to_string(),
+            PrintFormat::Hex => format!("{}{:#x}", sign, n.unsigned_abs()),
+            PrintFormat::Bin => format!("{}{:#b}", sign, n.unsigned_abs()),
+            PrintFormat::Oct => format!("{}{:#o}", sign, n.unsigned_abs()),
+        })
+    }
+
+    /// The text `print` shows for a value. Instances use their `toString`
+    /// method when their class defines one, including inside lists.
+    pub(crate) fn stringify(&mut self, value: &Object) -> Result<String, Unwind> {
+        value.display_with(&mut |item| self.repr_item(item))
+    }
+
+    /// Like `stringify`, but with strings quoted, as the REPL echoes values.
+    pub(crate) fn repr(&mut self, value: &Object) -> Result<String, Unwind> {
+        self.repr_item(value)
+    }
+
+    /// Like `stringify`, but laid out as `prettyPrint` shows lists.
+    pub(crate) fn pretty(&mut self, value: &Object) -> Result<String, Unwind> {
+        value.pretty_with(&mut |item| self.repr_item(item))
+    }
+
+    fn repr_item(&mut self, value: &Object) -> Result<String, Unwind> {
+        match value {
+            Object::Instance(instance) => LoxInstance::stringify(instance, self),
+            Object::String(_) => Ok(value.repr()),
+            Object::List(_) => self.stringify(value),
+            _ => Ok(value.to_string()),
+        }
+    }
+
+    fn check_number_operand(
+        &self,
+        operator: &Token,
+        operand: &Object,
     ) -> Result<f64, RuntimeError> {
         match operand {
             Object::Number(num) => Ok(*num),
@@ -321,6 +1194,16 @@ impl Interpreter {
         }
     }
 
+    fn check_nonzero_divisor(&self, operator: &Token, divisor: f64) -> Result<(), RuntimeError> {
+        if divisor == 0.0 {
+            return Err(RuntimeError {
+                message: "Division by zero.".to_string(),
+                token: operator.clone(),
+            });
+        }
+        Ok(())
+    }
+
     fn check_number_operands(
         &self,
         op: &Token,
@@ -336,20 +1219,95 @@ impl Interpreter {
         }
     }
 
+    /// Orders two numbers or two strings. `None` means the operands are
+    /// unordered, which only happens when a number is NaN.
+    fn compare_operands(
+        &self,
+        op: &Token,
+        left: &Object,
+        right: &Object,
+        chained: bool,
+    ) -> Result<Option<Ordering>, RuntimeError> {
+        match (left, right) {
+            (Object::Number(left_num), Object::Number(right_num)) => {
+                Ok(left_num.partial_cmp(right_num))
+            }
+            (Object::String(left_str), Object::String(right_str)) => {
+                Ok(Some(left_str.cmp(right_str)))
+            }
+            _ if chained => Err(RuntimeError {
+                message: "Chained comparison detected; use 'and' instead.".to_string(),
+                token: op.clone(),
+            }),
+            _ => Err(RuntimeError {
+                message: "Operands must be two numbers or two strings".to_string(),
+                token: op.clone(),
+            }),
+        }
+    }
+
+    fn is_comparison(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Binary(_, op, _) => matches!(
+                op.token_type,
+                TokenType::Greater
+                    | TokenType::GreaterEqual
+                    | TokenType::Less
+                    | TokenType::LessEqual
+            ),
+            _ => false,
+        }
+    }
+
     fn is_truthy(&self, value: &Object) -> bool {
-        match value {
-            Object::Nil => false,
-            Object::Boolean(b) => *b,
+        match (value, self.truthiness) {
+            (Object::Nil, _) => false,
+            (Object::Boolean(b), _) => *b,
+            (Object::Number(n), Truthiness::JsLike) => *n != 0.0 && !n.is_nan(),
+            (Object::String(s), Truthiness::JsLike) => !s.is_empty(),
             _ => true,
         }
     }
 
+    /// Checks that `index` is a whole number within a list of length `len`.
+    fn list_index(
+        &self,
+        bracket: &Token,
+        index: &Object,
+        len: usize,
+    ) -> Result<usize, RuntimeError> {
+        let index = match index {
+            Object::Number(n) if n.fract() == 0.0 => *n,
+            _ => {
+                return Err(RuntimeError {
+                    message: "List index must be an integer.".to_string(),
+                    token: bracket.clone(),
+                });
+            }
+        };
+        if index < 0.0 || index >= len as f64 {
+            return Err(RuntimeError {
+                message: format!(
+                    "Index {} is out of range for a list of length {}.",
+                    index, len
+                ),
+                token: bracket.clone(),
+            });
+        }
+        Ok(index as usize)
+    }
+
     fn is_equal(&self, a: &Object, b: &Object) -> bool {
         match (a, b) {
             (Object::Number(a), Object::Number(b)) => a == b,
             (Object::String(a), Object::String(b)) => a == b,
             (Object::Boolean(a), Object::Boolean(b)) => a == b,
             (Object::Nil, Object::Nil) => true,
+            (Object::Callable(a), Object::Callable(b)) => Rc::ptr_eq(a, b),
+            (Object::Class(a), Object::Class(b)) => Rc::ptr_eq(a, b),
+            (Object::Instance(a), Object::Instance(b)) => Rc::ptr_eq(a, b),
+            (Object::List(a), Object::List(b)) => Rc::ptr_eq(a, b),
+            (Object::Error(a), Object::Error(b)) => a == b,
             _ => false,
         }
     }
@@ -358,6 +1316,31 @@ impl Interpreter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::callable::Arity;
+    use crate::error_reporter::DiagnosticKind;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+    use crate::scanner::Scanner;
+    use crate::test_support::SharedBuffer;
+
+    fn run_source(source: &str) -> Interpreter {
+        run_source_in(Interpreter::new(), source)
+    }
+
+    fn run_source_in(mut interpreter: Interpreter, source: &str) -> Interpreter {
+        let mut parser = Parser::new(Scanner::new(source.to_string()).scan_tokens());
+        let statements = parser.parse();
+        assert!(!parser.error_reporter.had_error());
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        assert!(!resolver.error_reporter.had_error());
+        interpreter.interpret(statements);
+        interpreter
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Object {
+        interpreter.globals()[name].clone()
+    }
 
     #[test]
     fn test_interpret_addition() {
@@ -375,93 +1358,292 @@ mod tests {
         );
     }
 
-    #[test]
-    fn test_equality() {
-        let mut interpreter = Interpreter::new();
-        assert_eq!(
-            interpreter
-                .evaluate(&Expr::Binary(
-                    Box::new(Expr::Literal(Object::Number(1.0))),
-                    Token::new(TokenType::EqualEqual, "==".to_string(), None, 1),
-                    Box::new(Expr::Literal(Object::Number(1.0)))
-                ))
-                .unwrap(),
-            Object::Boolean(true)
-        );
+    fn runtime_error(result: Result<Object, Unwind>) -> RuntimeError {
+        match result {
+            Err(Unwind::Error(err)) => err,
+            other => panic!("Expected a runtime error, got {:?}", other),
+        }
     }
 
-    #[test]
-    fn test_interpret_variable_declaration_and_usage() {
+    fn concat(left: Object, right: Object) -> Result<Object, Unwind> {
         let mut interpreter = Interpreter::new();
-        let var_name = Token::new(TokenType::Identifier, "test_var".to_string(), None, 1);
-
-        let statements = vec![
-            // var test_var = 123;
-            Stmt::Var(
-                var_name.clone(),
-                Some(Box::new(Expr::Literal(Object::Number(123.0)))),
-            ),
-            // print test_var;
-            Stmt::Print(Box::new(Expr::Variable(var_name.clone()))),
-        ];
-
-        interpreter.interpret(statements);
+        interpreter.evaluate(&Expr::Binary(
+            Box::new(Expr::Literal(left)),
+            Token::new(TokenType::Plus, "+".to_string(), None, 1),
+            Box::new(Expr::Literal(right)),
+        ))
+    }
 
-        // Should not have any errors
-        assert!(!interpreter.error_reporter.had_runtime_error);
-        // Variable should exist in environment
+    #[test]
+    fn test_concatenate_string_with_each_variant() {
+        let prefix = || Object::String("x=".to_string());
         assert_eq!(
-            interpreter.environment.get(&var_name).unwrap(),
-            Object::Number(123.0)
+            concat(prefix(), Object::String("y".to_string())).unwrap(),
+            Object::String("x=y".to_string())
+        );
+        assert_eq!(
+            concat(prefix(), Object::Number(1.5)).unwrap(),
+            Object::String("x=1.5".to_string())
+        );
+        assert_eq!(
+            concat(prefix(), Object::Boolean(true)).unwrap(),
+            Object::String("x=true".to_string())
+        );
+        assert_eq!(
+            concat(prefix(), Object::Nil).unwrap(),
+            Object::String("x=nil".to_string())
+        );
+        assert_eq!(
+            concat(Object::Number(2.0), Object::String("nd".to_string())).unwrap(),
+            Object::String("2nd".to_string())
         );
     }
 
     #[test]
-    fn test_interpret_variable_reassignment() {
-        let mut interpreter = Interpreter::new();
-        let var_name = Token::new(TokenType::Identifier, "test_var".to_string(), None, 1);
+    fn test_concatenate_without_string_is_error() {
+        assert!(concat(Object::Boolean(true), Object::Nil).is_err());
+    }
 
-        let statements = vec![
-            // var test_var = 123;
-            Stmt::Var(
-                var_name.clone(),
-                Some(Box::new(Expr::Literal(Object::Number(123.0)))),
-            ),
-            // var test_var = 42;
-            Stmt::Var(
-                var_name.clone(),
-                Some(Box::new(Expr::Literal(Object::Number(42.0)))),
+    #[test]
+    fn test_add_objects_combinations() {
+        let mut interpreter = Interpreter::new();
+        let plus = Token::new(TokenType::Plus, "+".to_string(), None, 1);
+        let string = |s: &str| Object::String(s.to_string());
+        let valid = [
+            (
+                Object::Number(1.0),
+                Object::Number(2.0),
+                Object::Number(3.0),
             ),
+            (string("a"), string("b"), string("ab")),
+            (string("a"), Object::Number(1.0), string("a1")),
+            (Object::Number(1.0), string("a"), string("1a")),
+            (string("a"), Object::Boolean(false), string("afalse")),
+            (Object::Boolean(true), string("a"), string("truea")),
+            (string("a"), Object::Nil, string("anil")),
+            (Object::Nil, string("a"), string("nila")),
         ];
+        for (left, right, expected) in valid {
+            assert_eq!(
+                interpreter.add_objects(left, right, &plus).unwrap(),
+                expected
+            );
+        }
 
-        interpreter.interpret(statements);
+        let invalid = [
+            (Object::Number(1.0), Object::Boolean(true)),
+            (Object::Number(1.0), Object::Nil),
+            (Object::Boolean(true), Object::Boolean(false)),
+            (Object::Nil, Object::Nil),
+        ];
+        for (left, right) in invalid {
+            assert!(interpreter.add_objects(left, right, &plus).is_err());
+        }
+    }
 
-        // Should not have any errors
-        assert!(!interpreter.error_reporter.had_runtime_error);
-        // Variable should exist in environment
+    #[test]
+    fn test_chained_comparison_hint() {
+        // 1 < 2 < 3
+        let mut interpreter = Interpreter::new();
+        let less = Token::new(TokenType::Less, "<".to_string(), None, 1);
+        let err = runtime_error(interpreter.evaluate(&Expr::Binary(
+            Box::new(Expr::Binary(
+                Box::new(Expr::Literal(Object::Number(1.0))),
+                less.clone(),
+                Box::new(Expr::Literal(Object::Number(2.0))),
+            )),
+            less,
+            Box::new(Expr::Literal(Object::Number(3.0))),
+        )));
         assert_eq!(
-            interpreter.environment.get(&var_name).unwrap(),
-            Object::Number(42.0)
+            err.message,
+            "Chained comparison detected; use 'and' instead."
         );
     }
 
     #[test]
-    fn test_block_statement_scoping_and_shadowing() {
+    fn test_print_format_hex_and_bin() {
         let mut interpreter = Interpreter::new();
-
-        let var_a = Token::new(TokenType::Identifier, "a".to_string(), None, 1);
-        let var_b = Token::new(TokenType::Identifier, "b".to_string(), None, 1);
-
-        let statements = vec![
-            // var a = "global a";
-            Stmt::Var(
-                var_a.clone(),
-                Some(Box::new(Expr::Literal(Object::String(
-                    "global a".to_string(),
-                )))),
-            ),
-            // var b = "global b";
-            Stmt::Var(
+        assert_eq!(
+            interpreter
+                .format_value(&Object::Number(255.0), PrintFormat::Hex)
+                .unwrap(),
+            "0xff"
+        );
+        assert_eq!(
+            interpreter
+                .format_value(&Object::Number(5.0), PrintFormat::Bin)
+                .unwrap(),
+            "0b101"
+        );
+        assert_eq!(
+            interpreter
+                .format_value(&Object::Number(-8.0), PrintFormat::Oct)
+                .unwrap(),
+            "-0o10"
+        );
+        // Non-integral values ignore the format
+        assert_eq!(
+            interpreter
+                .format_value(&Object::Number(2.5), PrintFormat::Hex)
+                .unwrap(),
+            "2.5"
+        );
+        assert_eq!(
+            interpreter
+                .format_value(&Object::Number(1e300), PrintFormat::Hex)
+                .unwrap(),
+            "1e300"
+        );
+        assert_eq!(
+            interpreter
+                .format_value(&Object::Number(-9.3e18), PrintFormat::Bin)
+                .unwrap(),
+            "-9300000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_interpret_modulo() {
+        // 7 % 3
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .evaluate(&Expr::Binary(
+                    Box::new(Expr::Literal(Object::Number(7.0))),
+                    Token::new(TokenType::Percent, "%".to_string(), None, 1),
+                    Box::new(Expr::Literal(Object::Number(3.0)))
+                ))
+                .unwrap(),
+            Object::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let mut interpreter = Interpreter::new();
+        for (token_type, lexeme) in [(TokenType::Slash, "/"), (TokenType::Percent, "%")] {
+            let result = interpreter.evaluate(&Expr::Binary(
+                Box::new(Expr::Literal(Object::Number(5.0))),
+                Token::new(token_type, lexeme.to_string(), None, 1),
+                Box::new(Expr::Literal(Object::Number(0.0))),
+            ));
+            let err = runtime_error(result);
+            assert_eq!(err.message, "Division by zero.");
+            assert_eq!(err.token.lexeme, lexeme);
+        }
+    }
+
+    #[test]
+    fn test_nil_arithmetic() {
+        let interpreter = run_source("var sum = nil + 1;");
+        assert!(interpreter.error_reporter.had_runtime_error());
+
+        let mut lenient = Interpreter::new();
+        lenient.nil_arithmetic = true;
+        let lenient = run_source_in(
+            lenient,
+            "var sum = nil + 1;
+             var difference = 5 - nil;
+             var product = nil * 3;
+             var text = nil + \"!\";",
+        );
+        assert_eq!(global(&lenient, "sum"), Object::Number(1.0));
+        assert_eq!(global(&lenient, "difference"), Object::Number(5.0));
+        assert_eq!(global(&lenient, "product"), Object::Number(3.0));
+        assert_eq!(global(&lenient, "text"), Object::String("nil!".to_string()));
+    }
+
+    #[test]
+    fn test_equality() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(
+            interpreter
+                .evaluate(&Expr::Binary(
+                    Box::new(Expr::Literal(Object::Number(1.0))),
+                    Token::new(TokenType::EqualEqual, "==".to_string(), None, 1),
+                    Box::new(Expr::Literal(Object::Number(1.0)))
+                ))
+                .unwrap(),
+            Object::Boolean(true)
+        );
+    }
+
+    #[test]
+    fn test_interpret_variable_declaration_and_usage() {
+        let mut interpreter = Interpreter::new();
+        let var_name = Token::new(TokenType::Identifier, "test_var".to_string(), None, 1);
+
+        let statements = vec![
+            // var test_var = 123;
+            Stmt::Var(
+                var_name.clone(),
+                Some(Box::new(Expr::Literal(Object::Number(123.0)))),
+            ),
+            // print test_var;
+            Stmt::Print(
+                Box::new(Expr::Variable(var_name.clone(), Depth::default())),
+                PrintFormat::Default,
+            ),
+        ];
+
+        interpreter.interpret(statements);
+
+        // Should not have any errors
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // Variable should exist in environment
+        assert_eq!(
+            interpreter.environment.borrow().get(&var_name).unwrap(),
+            Object::Number(123.0)
+        );
+    }
+
+    #[test]
+    fn test_interpret_variable_reassignment() {
+        let mut interpreter = Interpreter::new();
+        let var_name = Token::new(TokenType::Identifier, "test_var".to_string(), None, 1);
+
+        let statements = vec![
+            // var test_var = 123;
+            Stmt::Var(
+                var_name.clone(),
+                Some(Box::new(Expr::Literal(Object::Number(123.0)))),
+            ),
+            // var test_var = 42;
+            Stmt::Var(
+                var_name.clone(),
+                Some(Box::new(Expr::Literal(Object::Number(42.0)))),
+            ),
+        ];
+
+        interpreter.interpret(statements);
+
+        // Should not have any errors
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // Variable should exist in environment
+        assert_eq!(
+            interpreter.environment.borrow().get(&var_name).unwrap(),
+            Object::Number(42.0)
+        );
+    }
+
+    #[test]
+    fn test_block_statement_scoping_and_shadowing() {
+        let mut interpreter = Interpreter::new();
+
+        let var_a = Token::new(TokenType::Identifier, "a".to_string(), None, 1);
+        let var_b = Token::new(TokenType::Identifier, "b".to_string(), None, 1);
+
+        let statements = vec![
+            // var a = "global a";
+            Stmt::Var(
+                var_a.clone(),
+                Some(Box::new(Expr::Literal(Object::String(
+                    "global a".to_string(),
+                )))),
+            ),
+            // var b = "global b";
+            Stmt::Var(
                 var_b.clone(),
                 Some(Box::new(Expr::Literal(Object::String(
                     "global b".to_string(),
@@ -490,15 +1672,15 @@ mod tests {
         interpreter.interpret(statements);
 
         // Should not have any errors
-        assert!(!interpreter.error_reporter.had_runtime_error);
+        assert!(!interpreter.error_reporter.had_runtime_error());
 
         // After all blocks have closed, variables should have their global values
         assert_eq!(
-            interpreter.environment.get(&var_a).unwrap(),
+            interpreter.environment.borrow().get(&var_a).unwrap(),
             Object::String("global a".to_string())
         );
         assert_eq!(
-            interpreter.environment.get(&var_b).unwrap(),
+            interpreter.environment.borrow().get(&var_b).unwrap(),
             Object::String("global b".to_string())
         );
     }
@@ -520,13 +1702,173 @@ mod tests {
                 )))),
             )]),
             // Try to access block_only variable outside the block - this should cause an error
-            Stmt::Print(Box::new(Expr::Variable(var_block_only.clone()))),
+            Stmt::Print(
+                Box::new(Expr::Variable(var_block_only.clone(), Depth::default())),
+                PrintFormat::Default,
+            ),
         ];
 
         interpreter.interpret(statements);
 
         // Should have a runtime error because block_only is not accessible outside the block
-        assert!(interpreter.error_reporter.had_runtime_error);
+        assert!(interpreter.error_reporter.had_runtime_error());
+    }
+
+    #[test]
+    fn test_assert_failure_is_runtime_error() {
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(vec![Stmt::Assert(
+            Token::new(TokenType::Assert, "assert".to_string(), None, 1),
+            Box::new(Expr::Literal(Object::Boolean(false))),
+        )]);
+        assert!(interpreter.error_reporter.had_runtime_error());
+    }
+
+    #[test]
+    fn test_disabled_assertions_skip_condition() {
+        let mut interpreter = Interpreter::new();
+        interpreter.assertions_enabled = false;
+        let var_a = Token::new(TokenType::Identifier, "a".to_string(), None, 1);
+
+        let statements = vec![
+            // var a = 0;
+            Stmt::Var(
+                var_a.clone(),
+                Some(Box::new(Expr::Literal(Object::Number(0.0)))),
+            ),
+            // assert (a = 1) and false;
+            Stmt::Assert(
+                Token::new(TokenType::Assert, "assert".to_string(), None, 1),
+                Box::new(Expr::Logical(
+                    Box::new(Expr::Assignment(
+                        var_a.clone(),
+                        Box::new(Expr::Literal(Object::Number(1.0))),
+                        Depth::default(),
+                    )),
+                    Token::new(TokenType::And, "and".to_string(), None, 1),
+                    Box::new(Expr::Literal(Object::Boolean(false))),
+                )),
+            ),
+        ];
+
+        interpreter.interpret(statements);
+
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // The assignment inside the condition never ran
+        assert_eq!(
+            interpreter.environment.borrow().get(&var_a).unwrap(),
+            Object::Number(0.0)
+        );
+    }
+
+    #[test]
+    fn test_exit_code_range() {
+        let exit_code = |source: &str| {
+            let mut interpreter = Interpreter::new();
+            let code = interpreter
+                .interpret(Parser::new(Scanner::new(source.to_string()).scan_tokens()).parse());
+            let errors: Vec<String> = interpreter
+                .error_reporter
+                .errors()
+                .iter()
+                .map(|error| error.message.clone())
+                .collect();
+            (code, errors)
+        };
+        assert_eq!(exit_code("exit 0;"), (Some(0), vec![]));
+        assert_eq!(exit_code("exit 255;"), (Some(255), vec![]));
+        for source in ["exit 256;", "exit -1;", "exit 1e20;", "exit 1.5;"] {
+            assert_eq!(
+                exit_code(source),
+                (
+                    None,
+                    vec!["Exit code must be an integer from 0 to 255.".to_string()]
+                ),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_exit_stops_execution() {
+        let mut interpreter = Interpreter::new();
+        let var_a = Token::new(TokenType::Identifier, "a".to_string(), None, 1);
+
+        let statements = vec![
+            // exit 3;
+            Stmt::Exit(
+                Token::new(TokenType::Exit, "exit".to_string(), None, 1),
+                Some(Box::new(Expr::Literal(Object::Number(3.0)))),
+            ),
+            // var a = 1;
+            Stmt::Var(
+                var_a.clone(),
+                Some(Box::new(Expr::Literal(Object::Number(1.0)))),
+            ),
+        ];
+
+        assert_eq!(interpreter.interpret(statements), Some(3));
+        // The declaration after exit should never have run
+        assert!(interpreter.environment.borrow().get(&var_a).is_err());
+    }
+
+    #[test]
+    fn test_print_expression_returns_value() {
+        let mut interpreter = Interpreter::new();
+        let var_y = Token::new(TokenType::Identifier, "y".to_string(), None, 1);
+
+        // var y = print 5;
+        interpreter.interpret(vec![Stmt::Var(
+            var_y.clone(),
+            Some(Box::new(Expr::Print(Box::new(Expr::Literal(
+                Object::Number(5.0),
+            ))))),
+        )]);
+
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(
+            interpreter.environment.borrow().get(&var_y).unwrap(),
+            Object::Number(5.0)
+        );
+    }
+
+    fn if_zero_takes_then_branch(interpreter: &mut Interpreter) -> bool {
+        let var_a = Token::new(TokenType::Identifier, "a".to_string(), None, 1);
+        let statements = vec![
+            // var a = false;
+            Stmt::Var(
+                var_a.clone(),
+                Some(Box::new(Expr::Literal(Object::Boolean(false)))),
+            ),
+            // if (0) a = true;
+            Stmt::If(
+                Box::new(Expr::Literal(Object::Number(0.0))),
+                Box::new(Stmt::Expression(Box::new(Expr::Assignment(
+                    var_a.clone(),
+                    Box::new(Expr::Literal(Object::Boolean(true))),
+                    Depth::default(),
+                )))),
+                None,
+            ),
+        ];
+        interpreter.interpret(statements);
+        interpreter.environment.borrow().get(&var_a).unwrap() == Object::Boolean(true)
+    }
+
+    #[test]
+    fn test_default_truthiness_zero_is_true() {
+        let mut interpreter = Interpreter::new();
+        assert!(if_zero_takes_then_branch(&mut interpreter));
+    }
+
+    #[test]
+    fn test_js_like_truthiness() {
+        let mut interpreter = Interpreter::new().with_truthiness(Truthiness::JsLike);
+        assert!(!if_zero_takes_then_branch(&mut interpreter));
+        assert!(!interpreter.is_truthy(&Object::String(String::new())));
+        assert!(!interpreter.is_truthy(&Object::Number(f64::NAN)));
+        assert!(interpreter.is_truthy(&Object::String("0".to_string())));
     }
 
     #[test]
@@ -606,7 +1948,11 @@ mod tests {
         let paren_token = Token::new(TokenType::LeftParen, "(".to_string(), None, 1);
 
         // Create call expression: clock()
-        let call_expr = Expr::Call(Box::new(Expr::Variable(clock_token)), paren_token, vec![]);
+        let call_expr = Expr::Call(
+            Box::new(Expr::Variable(clock_token, Depth::default())),
+            paren_token,
+            vec![],
+        );
 
         // Evaluate the call
         let result = interpreter.evaluate(&call_expr).unwrap();
@@ -636,7 +1982,7 @@ mod tests {
             // }
             Stmt::While(
                 Box::new(Expr::Binary(
-                    Box::new(Expr::Variable(var_a.clone())),
+                    Box::new(Expr::Variable(var_a.clone(), Depth::default())),
                     Token::new(TokenType::Less, "<".to_string(), None, 1),
                     Box::new(Expr::Literal(Object::Number(3.0))),
                 )),
@@ -644,23 +1990,1042 @@ mod tests {
                     Expr::Assignment(
                         var_a.clone(),
                         Box::new(Expr::Binary(
-                            Box::new(Expr::Variable(var_a.clone())),
+                            Box::new(Expr::Variable(var_a.clone(), Depth::default())),
                             Token::new(TokenType::Plus, "+".to_string(), None, 1),
                             Box::new(Expr::Literal(Object::Number(1.0))),
                         )),
+                        Depth::default(),
                     ),
                 ))])),
+                None,
             ),
         ];
 
         interpreter.interpret(statements);
 
         // Should not have any errors
-        assert!(!interpreter.error_reporter.had_runtime_error);
+        assert!(!interpreter.error_reporter.had_runtime_error());
         // Variable should have been incremented to 3
         assert_eq!(
-            interpreter.environment.get(&var_a).unwrap(),
+            interpreter.environment.borrow().get(&var_a).unwrap(),
             Object::Number(3.0)
         );
     }
+
+    #[test]
+    fn test_user_defined_function() {
+        let interpreter = run_source(
+            "fun add(a, b) { return a + b; }
+             var result = add(1, 2);",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "result"), Object::Number(3.0));
+    }
+
+    #[test]
+    fn test_recursive_function() {
+        let interpreter = run_source(
+            "fun fib(n) {
+               if (n < 2) return n;
+               return fib(n - 1) + fib(n - 2);
+             }
+             var result = fib(10);",
+        );
+        assert_eq!(global(&interpreter, "result"), Object::Number(55.0));
+    }
+
+    #[test]
+    fn test_function_without_return_yields_nil() {
+        let interpreter = run_source(
+            "fun noop() {}
+             var result = noop();",
+        );
+        assert_eq!(global(&interpreter, "result"), Object::Nil);
+    }
+
+    #[test]
+    fn test_function_does_not_see_caller_locals() {
+        let interpreter = run_source(
+            "fun peek() { return hidden; }
+             { var hidden = 1; peek(); }",
+        );
+        assert!(interpreter.error_reporter.had_runtime_error());
+    }
+
+    #[test]
+    fn test_while_else_runs_when_condition_fails() {
+        let interpreter = run_source(
+            "var i = 0;
+             var finished = false;
+             while (i < 3) { i = i + 1; } else finished = true;",
+        );
+        assert_eq!(global(&interpreter, "i"), Object::Number(3.0));
+        assert_eq!(global(&interpreter, "finished"), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_dangling_else_after_unbraced_while_belongs_to_if() {
+        let interpreter = run_source(
+            "var c = false;
+             var d = false;
+             var taken;
+             if (c) while (d) taken = \"while\"; else taken = \"else\";",
+        );
+        assert_eq!(
+            global(&interpreter, "taken"),
+            Object::String("else".to_string())
+        );
+    }
+
+    #[test]
+    fn test_continue_in_for_runs_increment() {
+        let interpreter = run_source(
+            "var odd = 0;
+             var last;
+             for (var i = 0; i < 6; i = i + 1) {
+                 last = i;
+                 if (i % 2 == 0) continue;
+                 odd = odd + 1;
+             }",
+        );
+        assert_eq!(global(&interpreter, "odd"), Object::Number(3.0));
+        assert_eq!(global(&interpreter, "last"), Object::Number(5.0));
+        assert!(!interpreter.globals().contains_key("i"));
+    }
+
+    #[test]
+    fn test_loop_runs_until_break() {
+        let interpreter = run_source(
+            "var i = 0;
+             var odd = 0;
+             loop {
+                 i = i + 1;
+                 if (i > 5) break;
+                 if (i % 2 == 0) continue;
+                 odd = odd + 1;
+             }",
+        );
+        assert_eq!(global(&interpreter, "i"), Object::Number(6.0));
+        assert_eq!(global(&interpreter, "odd"), Object::Number(3.0));
+    }
+
+    #[test]
+    fn test_unless_selects_branch() {
+        let interpreter = run_source(
+            "var a; var b;
+             unless (false) a = \"then\"; else a = \"else\";
+             unless (true) b = \"then\"; else b = \"else\";",
+        );
+        assert_eq!(
+            global(&interpreter, "a"),
+            Object::String("then".to_string())
+        );
+        assert_eq!(
+            global(&interpreter, "b"),
+            Object::String("else".to_string())
+        );
+    }
+
+    #[test]
+    fn test_assign_undeclared_variable_hint() {
+        let mut interpreter = Interpreter::new();
+        let var_x = Token::new(TokenType::Identifier, "x".to_string(), None, 1);
+
+        let assign = interpreter.evaluate(&Expr::Assignment(
+            var_x.clone(),
+            Box::new(Expr::Literal(Object::Number(1.0))),
+            Depth::default(),
+        ));
+        assert_eq!(
+            runtime_error(assign).message,
+            "Undefined variable 'x'. Did you mean 'var x = ...'?"
+        );
+
+        let read = interpreter.evaluate(&Expr::Variable(var_x, Depth::default()));
+        assert_eq!(runtime_error(read).message, "Undefined variable 'x'.");
+    }
+
+    #[test]
+    fn test_runtime_errors_are_recorded_as_diagnostics() {
+        let mut interpreter = Interpreter::new();
+        for (line, source) in ["print first;", "\nprint second;"].iter().enumerate() {
+            let mut parser = Parser::new(Scanner::new(source.to_string()).scan_tokens());
+            interpreter.interpret(parser.parse());
+            assert_eq!(interpreter.error_reporter.errors().len(), line + 1);
+        }
+
+        let errors = interpreter.error_reporter.errors();
+        assert_eq!(errors[0].message, "Undefined variable 'first'.");
+        assert_eq!(errors[1].message, "Undefined variable 'second'.");
+        assert_eq!(errors[1].line, 2);
+        assert!(
+            errors
+                .iter()
+                .all(|error| error.kind == DiagnosticKind::Runtime)
+        );
+        assert!(interpreter.error_reporter.had_runtime_error());
+        assert!(!interpreter.error_reporter.had_error());
+    }
+
+    #[test]
+    fn test_runtime_errors_in_each_top_level_statement() {
+        let interpreter = run_source(
+            "var a = nil + 1;
+             var b = -\"text\";
+             var ok = true;
+             undefined();",
+        );
+        let messages: Vec<(u32, &str)> = interpreter
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| (error.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (1, "Operands must be two numbers or two strings"),
+                (2, "Operand must be a number"),
+                (4, "Undefined variable 'undefined'."),
+            ]
+        );
+        assert_eq!(global(&interpreter, "ok"), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_native_function_through_variable() {
+        let interpreter = run_source(
+            "var timer = clock;
+             var now = timer();
+             var same = timer == clock;",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert!(matches!(global(&interpreter, "now"), Object::Number(_)));
+        assert_eq!(global(&interpreter, "same"), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_functions_compare_by_identity() {
+        let interpreter = run_source(
+            "fun a() {}
+             fun b() {}
+             var different = a == b;",
+        );
+        assert_eq!(global(&interpreter, "different"), Object::Boolean(false));
+    }
+
+    #[test]
+    fn test_closure_captures_enclosing_scope() {
+        let interpreter = run_source(
+            "fun makeCounter() {
+               var i = 0;
+               fun count() {
+                 i = i + 1;
+                 return i;
+               }
+               return count;
+             }
+             var counter = makeCounter();
+             var first = counter();
+             var second = counter();",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "first"), Object::Number(1.0));
+        assert_eq!(global(&interpreter, "second"), Object::Number(2.0));
+    }
+
+    #[test]
+    fn test_break_terminates_loop() {
+        let interpreter = run_source(
+            "var i = 0;
+             var finished = false;
+             while (true) { i = i + 1; if (i == 3) break; } else finished = true;",
+        );
+        assert_eq!(global(&interpreter, "i"), Object::Number(3.0));
+        assert_eq!(global(&interpreter, "finished"), Object::Boolean(false));
+    }
+
+    #[test]
+    fn test_continue_skips_rest_of_iteration() {
+        let interpreter = run_source(
+            "var i = 0;
+             var odd = 0;
+             while (i < 5) {
+               i = i + 1;
+               if (i % 2 == 0) continue;
+               odd = odd + 1;
+             }",
+        );
+        assert_eq!(global(&interpreter, "i"), Object::Number(5.0));
+        assert_eq!(global(&interpreter, "odd"), Object::Number(3.0));
+    }
+
+    #[test]
+    fn test_and_or_conditional_idiom() {
+        let interpreter = run_source(
+            "var picked = true and 1 or 2;
+             var fallback = false and 1 or 2;
+             var falsyMiddle = true and nil or 2;",
+        );
+        assert_eq!(global(&interpreter, "picked"), Object::Number(1.0));
+        assert_eq!(global(&interpreter, "fallback"), Object::Number(2.0));
+        // The idiom breaks down when the middle operand is itself falsy
+        assert_eq!(global(&interpreter, "falsyMiddle"), Object::Number(2.0));
+    }
+
+    #[test]
+    fn test_single_statement_loop_body() {
+        let interpreter = run_source(
+            "var i = 0;
+             while (i < 3) i = i + 1;",
+        );
+        assert_eq!(global(&interpreter, "i"), Object::Number(3.0));
+    }
+
+    #[test]
+    fn test_braced_loop_body_scope() {
+        let interpreter = run_source(
+            "var i = 0;
+             var first;
+             var second;
+             while (i < 2) {
+               var captured = i;
+               fun get() { return captured; }
+               if (i == 0) first = get; else second = get;
+               i = i + 1;
+             }
+             var a = first();
+             var b = second();",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // Each iteration gets its own scope, which doesn't leak out of the loop
+        assert!(!interpreter.globals().contains_key("captured"));
+        assert_eq!(global(&interpreter, "a"), Object::Number(0.0));
+        assert_eq!(global(&interpreter, "b"), Object::Number(1.0));
+    }
+
+    #[test]
+    fn test_string_comparison() {
+        let interpreter = run_source(
+            "var less = \"a\" < \"b\";
+             var greater = \"b\" < \"a\";
+             var prefix = \"app\" <= \"apple\";",
+        );
+        assert_eq!(global(&interpreter, "less"), Object::Boolean(true));
+        assert_eq!(global(&interpreter, "greater"), Object::Boolean(false));
+        assert_eq!(global(&interpreter, "prefix"), Object::Boolean(true));
+
+        let interpreter = run_source("var mixed = \"a\" < 1;");
+        assert!(interpreter.error_reporter.had_runtime_error());
+    }
+
+    #[test]
+    fn test_class_method_returns_field() {
+        let interpreter = run_source(
+            "class Box {
+               contents() { return this.value; }
+             }
+             var box = Box();
+             box.value = 42;
+             var contents = box.contents();
+             var described = \"\" + box;",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "contents"), Object::Number(42.0));
+        assert_eq!(
+            global(&interpreter, "described"),
+            Object::String("Box instance".to_string())
+        );
+    }
+
+    #[test]
+    fn test_class_initializer() {
+        let interpreter = run_source(
+            "class Point {
+               init(x, y) { this.x = x; this.y = y; }
+               sum() { return this.x + this.y; }
+             }
+             var point = Point(1, 2);
+             var sum = point.sum();
+             var method = point.sum;
+             var bound = method();
+             var again = point.init(3, 4) == point;",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "sum"), Object::Number(3.0));
+        assert_eq!(global(&interpreter, "bound"), Object::Number(3.0));
+        assert_eq!(global(&interpreter, "again"), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_property_on_non_instance() {
+        let interpreter = run_source("var n = 1; var x = n.field;");
+        assert!(interpreter.error_reporter.had_runtime_error());
+    }
+
+    #[test]
+    fn test_print_uses_to_string_method() {
+        let buffer = SharedBuffer::default();
+        let interpreter = run_source_in(
+            Interpreter::new().with_output(Box::new(buffer.clone())),
+            "class Point {
+                 init(x, y) { this.x = x; this.y = y; }
+                 toString() { return \"(\" + this.x + \", \" + this.y + \")\"; }
+             }
+             class Plain {}
+             print Point(1, 2);
+             print Plain();",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(buffer.contents(), "(1, 2)\nPlain instance\n");
+    }
+
+    #[test]
+    fn test_to_string_used_by_every_display_path() {
+        let buffer = SharedBuffer::default();
+        let interpreter = run_source_in(
+            Interpreter::new().with_output(Box::new(buffer.clone())),
+            "class Point { toString() { return \"P\"; } }
+             var p = Point();
+             print [p, \"s\", [p]];
+             print str(p);
+             print join([p, 1], \",\");
+             print \"x\" + p;
+             print p + \"x\";
+             prettyPrint([p]);",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(
+            buffer.contents(),
+            "[P, \"s\", [P]]\nP\nP,1\nxP\nPx\n[\n  P\n]\n"
+        );
+    }
+
+    #[test]
+    fn test_to_string_counts_towards_call_depth() {
+        let mut interpreter = Interpreter::new();
+        interpreter.max_call_depth = 50;
+        let interpreter = run_source_in(
+            interpreter,
+            "class Loop { toString() { return str(this); } } print Loop();",
+        );
+        assert_eq!(
+            interpreter.error_reporter.errors()[0].message,
+            "Stack overflow."
+        );
+    }
+
+    #[test]
+    fn test_to_string_must_return_string() {
+        let buffer = SharedBuffer::default();
+        let interpreter = run_source_in(
+            Interpreter::new().with_output(Box::new(buffer.clone())),
+            "class Broken { toString() { return 1; } } print Broken();",
+        );
+        assert_eq!(
+            interpreter.error_reporter.errors()[0].message,
+            "toString must return a string."
+        );
+        assert_eq!(buffer.contents(), "");
+    }
+
+    #[test]
+    fn test_fluent_method_chain() {
+        let interpreter = run_source(
+            "class Builder {
+                 init() { this.total = 0; this.calls = 0; }
+                 add(n) {
+                     this.total = this.total + n;
+                     this.calls = this.calls + 1;
+                     return this;
+                 }
+                 build() { return this.total * 10 + this.calls; }
+             }
+             var builder = Builder();
+             var built = builder.add(1).add(2).add(3).build();
+             var same = builder.add(0) == builder;",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "built"), Object::Number(63.0));
+        assert_eq!(global(&interpreter, "same"), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_instances_are_passed_by_reference() {
+        let interpreter = run_source(
+            "class Counter {}
+             fun bump(counter, n) {
+               counter.count = counter.count + 1;
+               n = n + 1;
+             }
+             var counter = Counter();
+             counter.count = 0;
+             var n = 0;
+             bump(counter, n);
+             var count = counter.count;",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // The instance is shared with the caller, the number is copied
+        assert_eq!(global(&interpreter, "count"), Object::Number(1.0));
+        assert_eq!(global(&interpreter, "n"), Object::Number(0.0));
+    }
+
+    #[test]
+    fn test_lists_are_passed_by_reference() {
+        let interpreter = run_source(
+            "fun append(items, n) {
+               push(items, n);
+               n = n + 1;
+             }
+             var items = [1];
+             var n = 2;
+             append(items, n);",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // Pushing inside the function changes the caller's list
+        assert_eq!(global(&interpreter, "items").to_string(), "[1, 2]");
+        assert_eq!(global(&interpreter, "n"), Object::Number(2.0));
+    }
+
+    #[test]
+    fn test_inheritance_and_super() {
+        let interpreter = run_source(
+            "class Animal {
+               init(name) { this.name = name; }
+               speak() { return this.name + \" makes a sound\"; }
+               kind() { return \"animal\"; }
+             }
+             class Dog < Animal {
+               speak() { return super.speak() + \" and barks\"; }
+             }
+             var dog = Dog(\"Rex\");
+             var speech = dog.speak();
+             var kind = dog.kind();",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(
+            global(&interpreter, "speech"),
+            Object::String("Rex makes a sound and barks".to_string())
+        );
+        assert_eq!(
+            global(&interpreter, "kind"),
+            Object::String("animal".to_string())
+        );
+    }
+
+    #[test]
+    fn test_superclass_must_be_class() {
+        let interpreter = run_source("var NotAClass = 1; class Oops < NotAClass {}");
+        assert!(interpreter.error_reporter.had_runtime_error());
+    }
+
+    #[test]
+    fn test_closure_keeps_resolved_binding() {
+        let interpreter = run_source(
+            "var a = \"global\";
+             var first;
+             var second;
+             {
+               fun showA() { return a; }
+               first = showA();
+               var a = \"block\";
+               second = showA();
+             }",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // The later local declaration must not change what showA sees
+        assert_eq!(
+            global(&interpreter, "first"),
+            Object::String("global".to_string())
+        );
+        assert_eq!(
+            global(&interpreter, "second"),
+            Object::String("global".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unbounded_recursion_is_stack_overflow() {
+        // Test threads get a small stack, so keep well under the default
+        let mut interpreter = Interpreter::new();
+        interpreter.max_call_depth = 50;
+        let interpreter = run_source_in(
+            interpreter,
+            "fun f(n) { return f(n + 1); }
+             f(0);
+             var after = true;",
+        );
+        assert_eq!(
+            interpreter.error_reporter.errors()[0].message,
+            "Stack overflow."
+        );
+        assert_eq!(interpreter.max_depth_reached(), interpreter.max_call_depth);
+        // The error unwinds every call, so the program carries on
+        assert_eq!(global(&interpreter, "after"), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_max_depth_reached() {
+        let interpreter = run_source(
+            "fun countdown(n) { if (n > 0) countdown(n - 1); }
+             countdown(4);
+             countdown(2);",
+        );
+        // countdown(4) down to countdown(0) is five nested calls
+        assert_eq!(interpreter.max_depth_reached(), 5);
+    }
+
+    #[test]
+    fn test_stray_break_and_continue_are_runtime_errors() {
+        let mut parser = Parser::new(
+            Scanner::new(
+                "break;
+                 fun f() { continue; }
+                 var stopped = false;
+                 while (!stopped) { stopped = true; f(); }"
+                    .to_string(),
+            )
+            .scan_tokens(),
+        );
+        let statements = parser.parse();
+        assert!(parser.error_reporter.had_error());
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(statements);
+        let messages: Vec<&str> = interpreter
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        // A continue escaping a function doesn't reach the caller's loop
+        assert_eq!(
+            messages,
+            [
+                "Can't use 'break' outside of a loop.",
+                "Can't use 'continue' outside of a loop.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_level_defer_is_runtime_error() {
+        let buffer = SharedBuffer::default();
+        let mut parser = Parser::new(Scanner::new("defer print 1;".to_string()).scan_tokens());
+        let statements = parser.parse();
+        assert!(parser.error_reporter.had_error());
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        interpreter.interpret(statements);
+        assert_eq!(
+            interpreter.error_reporter.errors()[0].message,
+            "Can't use 'defer' outside of a function."
+        );
+        assert_eq!(buffer.contents(), "");
+    }
+
+    #[test]
+    fn test_top_level_return_is_runtime_error() {
+        // The parser reports the return, but a host may interpret it anyway
+        let mut parser =
+            Parser::new(Scanner::new("return 1; var after = 2;".to_string()).scan_tokens());
+        let statements = parser.parse();
+        assert!(parser.error_reporter.had_error());
+        let mut interpreter = Interpreter::new();
+        interpreter.interpret(statements);
+        assert_eq!(
+            interpreter.error_reporter.errors()[0].message,
+            "Can't return from top-level code."
+        );
+        assert_eq!(global(&interpreter, "after"), Object::Number(2.0));
+    }
+
+    #[test]
+    fn test_defer_runs_before_returning_to_caller() {
+        let interpreter = run_source(
+            "var log = \"\";
+             fun work() {
+               defer log = log + \"first deferred;\";
+               defer log = log + \"second deferred;\";
+               log = log + \"body;\";
+               if (true) return log;
+               log = log + \"unreachable;\";
+             }
+             var returned = work();
+             log = log + \"caller;\";",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        // The return value is computed before the deferred statements run
+        assert_eq!(
+            global(&interpreter, "returned"),
+            Object::String("body;".to_string())
+        );
+        assert_eq!(
+            global(&interpreter, "log"),
+            Object::String("body;second deferred;first deferred;caller;".to_string())
+        );
+    }
+
+    #[test]
+    fn test_defer_runs_after_runtime_error() {
+        let interpreter = run_source(
+            "var cleaned = false;
+             fun fails() { defer cleaned = true; return 1 + nil; }
+             fails();",
+        );
+        assert!(interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "cleaned"), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_restore_snapshot() {
+        let interpreter = run_source("var x = 1; fun getX() { return x; }");
+        let snapshot = interpreter.snapshot();
+        let mut interpreter = run_source_in(interpreter, "x = 2; var y = 3; clock = nil;");
+        assert_eq!(global(&interpreter, "x"), Object::Number(2.0));
+
+        interpreter.restore(snapshot);
+        let interpreter = run_source_in(interpreter, "var seen = getX(); var now = clock();");
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "x"), Object::Number(1.0));
+        assert_eq!(global(&interpreter, "seen"), Object::Number(1.0));
+        assert!(!interpreter.globals().contains_key("y"));
+    }
+
+    #[test]
+    fn test_list_indexing() {
+        let interpreter = run_source(
+            "var xs = [10, [20, 30], \"forty\"];
+             var first = xs[0];
+             var nested = xs[1][1];
+             var i = 1;
+             var computed = xs[i + 1];",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "first"), Object::Number(10.0));
+        assert_eq!(global(&interpreter, "nested"), Object::Number(30.0));
+        assert_eq!(
+            global(&interpreter, "computed"),
+            Object::String("forty".to_string())
+        );
+    }
+
+    #[test]
+    fn test_index_assignment_evaluates_to_value() {
+        let buffer = SharedBuffer::default();
+        let interpreter = run_source_in(
+            Interpreter::new().with_output(Box::new(buffer.clone())),
+            "var a = [0]; print (a[0] = 7); var first = a[0];",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(buffer.contents(), "7\n");
+        assert_eq!(global(&interpreter, "first"), Object::Number(7.0));
+    }
+
+    #[test]
+    fn test_list_element_assignment() {
+        let interpreter = run_source(
+            "var xs = [1, 2, 3];
+             var alias = xs;
+             var result = xs[0] = 42;
+             alias[2] = xs[0] + 1;
+             var first = xs[0];
+             var last = xs[2];
+             xs[3] = 4;",
+        );
+        assert_eq!(global(&interpreter, "result"), Object::Number(42.0));
+        assert_eq!(global(&interpreter, "first"), Object::Number(42.0));
+        // Both names refer to the same list
+        assert_eq!(global(&interpreter, "last"), Object::Number(43.0));
+        assert_eq!(
+            interpreter.error_reporter.errors()[0].message,
+            "Index 3 is out of range for a list of length 3."
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment() {
+        let interpreter = run_source(
+            "var x = 1;
+             x += 4;
+             var y = 10;
+             y -= 2; y *= 3; y /= 4;
+             class Counter {}
+             var counter = Counter();
+             counter.count = 1;
+             counter.count += 1;
+             var count = counter.count;
+             var xs = [1, 2];
+             xs[1] *= 5;
+             var element = xs[1];
+             var s = \"a\";
+             s += \"b\";",
+        );
+        assert_eq!(global(&interpreter, "x"), Object::Number(5.0));
+        assert_eq!(global(&interpreter, "y"), Object::Number(6.0));
+        assert_eq!(global(&interpreter, "count"), Object::Number(2.0));
+        assert_eq!(global(&interpreter, "element"), Object::Number(10.0));
+        assert_eq!(global(&interpreter, "s"), Object::String("ab".to_string()));
+    }
+
+    #[test]
+    fn test_multiple_return_values() {
+        let interpreter = run_source(
+            "fun divide(a, b) { return floor(a / b), a % b; }
+             var quotient, remainder = divide(17, 5);
+             var total;
+             {
+                 var first, second = divide(9, 2);
+                 total = first + second;
+             }",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "quotient"), Object::Number(3.0));
+        assert_eq!(global(&interpreter, "remainder"), Object::Number(2.0));
+        assert_eq!(global(&interpreter, "total"), Object::Number(5.0));
+    }
+
+    #[test]
+    fn test_unpack_into_existing_variables() {
+        let interpreter = run_source(
+            "fun pair() { return 1, 2; }
+             var a;
+             var b;
+             a, b = pair();
+             var swapped;
+             {
+                 var x = 1;
+                 var y = 2;
+                 x, y = [y, x];
+                 swapped = x * 10 + y;
+             }",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "a"), Object::Number(1.0));
+        assert_eq!(global(&interpreter, "b"), Object::Number(2.0));
+        assert_eq!(global(&interpreter, "swapped"), Object::Number(21.0));
+    }
+
+    #[test]
+    fn test_unpack_errors() {
+        let interpreter = run_source(
+            "fun pair() { return 1, 2; }
+             var a, b, c = pair();
+             var d, e = 1;
+             var f;
+             var g;
+             f, g = [1, 2, 3];
+             f, missing = pair();",
+        );
+        let messages: Vec<&str> = interpreter
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Expected 3 values to unpack but got 2.",
+                "Can only unpack a list, got a number.",
+                "Expected 2 values to unpack but got 3.",
+                "Undefined variable 'missing'. Did you mean 'var missing = ...'?",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prelude_functions_are_defined() {
+        let interpreter = run_source(
+            "var clamped = clamp(15, 0, 10);
+             var numbers = range(1, 4);",
+        );
+        assert_eq!(global(&interpreter, "clamped"), Object::Number(10.0));
+        assert_eq!(global(&interpreter, "numbers").to_string(), "[1, 2, 3]");
+
+        let bare = Interpreter::without_prelude();
+        assert!(!bare.globals().contains_key("clamp"));
+        assert!(bare.globals().contains_key("clock"));
+    }
+
+    #[test]
+    fn test_compose_and_partial() {
+        let interpreter = run_source(
+            "fun inc(x) { return x + 1; }
+             fun double(x) { return x * 2; }
+             var composed = compose(inc, double)(3);
+             fun add(a, b, c) { return a + b + c; }
+             var addTen = partial(add, 10);
+             var partly = addTen(1, 2);
+             var fully = partial(add, 1, 2, 3)();
+             var chained = compose(inc, partial(add, 1, 1))(5);",
+        );
+        assert_eq!(global(&interpreter, "composed"), Object::Number(7.0));
+        assert_eq!(global(&interpreter, "partly"), Object::Number(13.0));
+        assert_eq!(global(&interpreter, "fully"), Object::Number(6.0));
+        assert_eq!(global(&interpreter, "chained"), Object::Number(8.0));
+    }
+
+    #[test]
+    fn test_compose_and_partial_errors() {
+        let interpreter = run_source(
+            "fun add(a, b) { return a + b; }
+             partial(add, 1)(2, 3);
+             partial(add, 1, 2, 3);
+             compose(add, add);
+             compose(1, add);",
+        );
+        let messages: Vec<&str> = interpreter
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Expected 1 arguments but got 2",
+                "Can't bind 3 arguments to a function that takes 2.",
+                "First argument to compose must take one argument.",
+                "Arguments to compose must be functions.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compound_assignment_on_this_field() {
+        let interpreter = run_source(
+            "class Counter {
+                 init() { this.count = 0; }
+                 increment() {
+                     this.count = this.count + 1;
+                     this.count += 1;
+                 }
+             }
+             var counter = Counter();
+             counter.increment();
+             counter.increment();
+             var count = counter.count;",
+        );
+        assert_eq!(global(&interpreter, "count"), Object::Number(4.0));
+    }
+
+    #[test]
+    fn test_compound_assignment_evaluates_target_once() {
+        let interpreter = run_source(
+            "var calls = 0;
+             fun next() { calls = calls + 1; return calls - 1; }
+             var xs = [10, 20];
+             xs[next()] += 5;
+             var first = xs[0];
+             var second = xs[1];
+             class Box {}
+             var box = Box();
+             box.count = 1;
+             var made = 0;
+             fun make() { made = made + 1; return box; }
+             make().count += 2;
+             var count = box.count;",
+        );
+        assert_eq!(global(&interpreter, "calls"), Object::Number(1.0));
+        assert_eq!(global(&interpreter, "first"), Object::Number(15.0));
+        assert_eq!(global(&interpreter, "second"), Object::Number(20.0));
+        assert_eq!(global(&interpreter, "made"), Object::Number(1.0));
+        assert_eq!(global(&interpreter, "count"), Object::Number(3.0));
+    }
+
+    #[test]
+    fn test_list_index_errors() {
+        let interpreter = run_source(
+            "var xs = [1, 2, 3];
+             xs[3];
+             xs[-1];
+             xs[0.5];
+             xs[\"0\"];
+             \"text\"[0];",
+        );
+        let messages: Vec<&str> = interpreter
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Index 3 is out of range for a list of length 3.",
+                "Index -1 is out of range for a list of length 3.",
+                "List index must be an integer.",
+                "List index must be an integer.",
+                "Only lists can be indexed.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_print_to_injected_writer() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        let mut parser = Parser::new(Scanner::new("print \"hi\";".to_string()).scan_tokens());
+        interpreter.interpret(parser.parse());
+        assert_eq!(buffer.contents(), "hi\n");
+    }
+
+    #[test]
+    fn test_interpret_collect() {
+        let mut interpreter = Interpreter::new();
+        let mut parser =
+            Parser::new(Scanner::new("1 + 2; var a = 4; a * 2;".to_string()).scan_tokens());
+        assert_eq!(
+            interpreter.interpret_collect(parser.parse()).unwrap(),
+            (vec![Object::Number(3.0), Object::Number(8.0)], None)
+        );
+
+        let mut parser = Parser::new(Scanner::new("1; exit 4; 2;".to_string()).scan_tokens());
+        assert_eq!(
+            interpreter.interpret_collect(parser.parse()).unwrap(),
+            (vec![Object::Number(1.0)], Some(4))
+        );
+
+        let mut parser = Parser::new(Scanner::new("-\"x\";".to_string()).scan_tokens());
+        let err = interpreter.interpret_collect(parser.parse()).unwrap_err();
+        assert_eq!(err.message, "Operand must be a number");
+    }
+
+    #[test]
+    fn test_mutual_recursion_at_top_level() {
+        let interpreter = run_source(
+            "fun isEven(n) { if (n == 0) return true; return isOdd(n - 1); }
+             fun isOdd(n) { if (n == 0) return false; return isEven(n - 1); }
+             var ten = isEven(10);
+             var seven = isEven(7);",
+        );
+        assert!(!interpreter.error_reporter.had_runtime_error());
+        assert_eq!(global(&interpreter, "ten"), Object::Boolean(true));
+        assert_eq!(global(&interpreter, "seven"), Object::Boolean(false));
+    }
+
+    #[test]
+    fn test_arity_range_error() {
+        let interpreter = run_source("round(1, 2, 3);");
+        assert!(interpreter.error_reporter.had_runtime_error());
+        assert_eq!(Arity::Range(1, 2).to_string(), "1 to 2");
+    }
+
+    #[test]
+    fn test_print_flushes_output() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        let mut parser = Parser::new(Scanner::new("print 1; print 2;".to_string()).scan_tokens());
+        interpreter.interpret(parser.parse());
+        assert_eq!(buffer.flushes(), 2);
+
+        // Without auto flush, only an explicit flush() does it
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        interpreter.auto_flush = false;
+        let mut parser =
+            Parser::new(Scanner::new("print 1; print 2; flush();".to_string()).scan_tokens());
+        interpreter.interpret(parser.parse());
+        assert_eq!(buffer.contents(), "1\n2\n");
+        assert_eq!(buffer.flushes(), 1);
+    }
 }
@@ -0,0 +1,111 @@
+//! A tree-walking interpreter for Lox.
+//!
+//! Running a program takes four passes: scan the source into tokens, parse
+//! the tokens into statements, resolve variable scopes, then interpret.
+//!
+//! ```
+//! use lox_tree_rust::{Interpreter, Parser, Resolver, Scanner};
+//!
+//! let mut scanner = Scanner::new("print 1 + 2;".to_string());
+//! let mut parser = Parser::new(scanner.scan_tokens());
+//! let statements = parser.parse();
+//! let mut resolver = Resolver::new();
+//! resolver.resolve(&statements);
+//! let mut interpreter = Interpreter::new();
+//! interpreter.interpret(statements);
+//! assert!(!interpreter.error_reporter.had_runtime_error());
+//! ```
+
+pub mod callable;
+pub mod class;
+pub mod environment;
+pub mod error_reporter;
+pub mod expressions;
+pub mod interpreter;
+mod natives;
+pub mod parser;
+pub mod repl;
+pub mod resolver;
+pub mod scanner;
+pub mod statements;
+pub mod tokens;
+
+#[cfg(test)]
+mod test_support;
+
+pub use error_reporter::{Diagnostic, DiagnosticKind};
+pub use expressions::Expr;
+pub use interpreter::Interpreter;
+pub use parser::Parser;
+pub use resolver::Resolver;
+pub use scanner::Scanner;
+pub use statements::Stmt;
+pub use tokens::{Object, Token};
+
+/// Runs every static check on `source` without executing it.
+pub fn check(source: &str) -> Vec<Diagnostic> {
+    let mut scanner = Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens();
+    scanner.check_delimiters();
+    let mut parser = Parser::new(tokens).with_source(source);
+    let statements = parser.parse();
+    let mut resolver = Resolver::new().with_source(source);
+    resolver.resolve(&statements);
+    let mut errors = scanner.error_reporter.errors().to_vec();
+    errors.extend_from_slice(parser.error_reporter.errors());
+    errors.extend_from_slice(resolver.error_reporter.errors());
+    errors
+}
+
+/// Checks and then runs `source` on `interpreter`, returning the exit code if
+/// the program called `exit`. Static errors stop the program before it starts
+/// and are returned; runtime errors are left on the interpreter's reporter.
+pub fn run(source: &str, interpreter: &mut Interpreter) -> Result<Option<i32>, Vec<Diagnostic>> {
+    let mut scanner = Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens();
+    scanner.check_delimiters();
+    if scanner.error_reporter.had_error() {
+        return Err(scanner.error_reporter.errors().to_vec());
+    }
+    let mut parser = Parser::new(tokens).with_source(source);
+    let statements = parser.parse();
+    if parser.error_reporter.had_error() {
+        return Err(parser.error_reporter.errors().to_vec());
+    }
+    let mut resolver = Resolver::new().with_source(source);
+    resolver.resolve(&statements);
+    if resolver.error_reporter.had_error() {
+        return Err(resolver.error_reporter.errors().to_vec());
+    }
+    Ok(interpreter.interpret(statements))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_reports_static_errors() {
+        let errors = check("print \"unreachable\";\nreturn 1;");
+        assert_eq!(
+            errors,
+            [Diagnostic {
+                line: 2,
+                column: Some(1),
+                message: "Can't return from top-level code.".to_string(),
+                kind: DiagnosticKind::Parse,
+            }]
+        );
+        assert!(check("print 1;").is_empty());
+    }
+
+    #[test]
+    fn test_run_stops_on_static_errors() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(run("var a = 1; exit a + 1;", &mut interpreter), Ok(Some(2)));
+
+        let errors = run("var b = ;", &mut interpreter).unwrap_err();
+        assert_eq!(errors[0].message, "Expect expression.");
+        assert!(!interpreter.globals().contains_key("b"));
+    }
+}
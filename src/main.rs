@@ -1,30 +1,27 @@
 use std::env;
 use std::error::Error;
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Read, Write};
 use std::path::Path;
 use std::process::exit;
 
-mod callable;
-mod environment;
-mod error_reporter;
-mod expressions;
-mod interpreter;
-mod parser;
-mod scanner;
-mod statements;
-mod tokens;
-
-use error_reporter::ErrorReporter;
-use interpreter::Interpreter;
-use parser::Parser;
-use scanner::Scanner;
+use lox_tree_rust::error_reporter::ErrorReporter;
+use lox_tree_rust::repl::{self, CommandResult};
+use lox_tree_rust::{DiagnosticKind, Interpreter, Parser, Scanner};
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args: Vec<String> = env::args().collect();
-    if args.len() > 2 {
-        println!("Usage: rlox <script>");
+    if args.len() == 3 && args[1] == "--check" {
+        check_file(Path::new(&args[2]))?;
+    } else if args.len() == 3 && args[1] == "--ast" {
+        print_ast(Path::new(&args[2]))?;
+    } else if args.len() > 2 {
+        println!("Usage: rlox [--check | --ast] <script | ->");
+    } else if args.len() == 2 && args[1] == "-" {
+        run_stdin()?;
     } else if args.len() == 2 {
         run_file(Path::new(&args[1]))?;
+    } else if !io::stdin().is_terminal() {
+        run_stdin()?;
     } else {
         run_prompt()?;
     }
@@ -34,11 +31,48 @@ fn main() -> Result<(), Box<dyn Error>> {
 
 fn run_file(path: &Path) -> Result<(), io::Error> {
     let contents = std::fs::read_to_string(path)?;
-    run(contents);
+    let mut interpreter = Interpreter::new();
+    run(contents, &mut interpreter);
+    Ok(())
+}
+
+/// Runs a whole program piped in on standard input, e.g. `echo 'print 1;' | rlox -`.
+fn run_stdin() -> Result<(), io::Error> {
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents)?;
+    let mut interpreter = Interpreter::new();
+    run(contents, &mut interpreter);
+    Ok(())
+}
+
+fn check_file(path: &Path) -> Result<(), io::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    let diagnostics = lox_tree_rust::check(&contents);
+    // Warnings are printed but don't fail the check
+    if diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.kind != DiagnosticKind::Warning)
+    {
+        exit(65);
+    }
+    Ok(())
+}
+
+fn print_ast(path: &Path) -> Result<(), io::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    let mut scanner = Scanner::new(contents.clone());
+    let mut parser = Parser::new(scanner.scan_tokens()).with_source(&contents);
+    let statements = parser.parse();
+    check_errors(&scanner.error_reporter);
+    check_errors(&parser.error_reporter);
+    for statement in statements {
+        println!("{}", statement);
+    }
     Ok(())
 }
 
 fn run_prompt() -> Result<(), io::Error> {
+    let mut interpreter = Interpreter::new();
     loop {
         let mut line = String::new();
         print!("> ");
@@ -47,28 +81,33 @@ fn run_prompt() -> Result<(), io::Error> {
         if line.trim().is_empty() {
             break;
         }
-        run(line);
+        if repl::is_command(&line) {
+            match repl::handle_command(&interpreter, &line) {
+                CommandResult::Output(output) => println!("{}", output),
+                CommandResult::Quit => break,
+            }
+            continue;
+        }
+        if let Some(code) = repl::run_line(&mut interpreter, &line) {
+            exit(code);
+        }
     }
     Ok(())
 }
 
-fn run(source: String) {
-    let mut scanner = Scanner::new(source);
-    let tokens = scanner.scan_tokens();
-    check_errors(&scanner.error_reporter);
-    let mut parser = Parser::new(tokens);
-    let statements = parser.parse();
-    check_errors(&parser.error_reporter);
-    let mut interpreter = Interpreter::new();
-    interpreter.interpret(statements);
-    check_errors(&interpreter.error_reporter);
+fn run(source: String, interpreter: &mut Interpreter) {
+    match lox_tree_rust::run(&source, interpreter) {
+        Err(_) => exit(65),
+        Ok(Some(code)) => exit(code),
+        Ok(None) => check_errors(&interpreter.error_reporter),
+    }
 }
 
 fn check_errors(error_reporter: &ErrorReporter) {
-    if error_reporter.had_error {
+    if error_reporter.had_error() {
         exit(65);
     }
-    if error_reporter.had_runtime_error {
+    if error_reporter.had_runtime_error() {
         exit(70);
     }
 }
@@ -0,0 +1,987 @@
+use std::rc::Rc;
+
+use crate::callable::{Arity, Callable, Composed, NativeCallable, NativeError, NativeFn, Partial};
+use crate::environment::Environment;
+use crate::tokens::Object;
+
+pub fn define_natives(env: &mut Environment) {
+    // Seconds since the Unix epoch, with sub-second precision
+    let clock = NativeCallable::new(Arity::Exact(0), |interpreter, _| {
+        Ok(Object::Number(interpreter.now().as_secs_f64()))
+    });
+    env.define_global("clock", Object::Callable(Rc::new(clock)));
+
+    // Returns a new value; the argument is left untouched
+    let reverse = NativeCallable::new(Arity::Exact(1), |_, args| match &args[0] {
+        Object::String(s) => Ok(Object::String(s.chars().rev().collect())),
+        Object::List(list) => Ok(Object::from(
+            list.borrow().iter().rev().cloned().collect::<Vec<Object>>(),
+        )),
+        _ => Err("Argument to reverse must be a list or a string."
+            .to_string()
+            .into()),
+    });
+    env.define_global("reverse", Object::Callable(Rc::new(reverse)));
+
+    // A string that doesn't parse gives an error value rather than raising,
+    // so scripts can recover with isError. Wrong argument types still raise.
+    let parse_int = NativeCallable::new(Arity::Exact(2), |_, args| {
+        let s: String = args[0].clone().try_into()?;
+        let radix: f64 = args[1].clone().try_into()?;
+        if radix.fract() != 0.0 || !(2.0..=36.0).contains(&radix) {
+            return Err("Radix must be an integer between 2 and 36."
+                .to_string()
+                .into());
+        }
+        Ok(match i64::from_str_radix(s.trim(), radix as u32) {
+            Ok(n) => Object::from(n as f64),
+            Err(_) => Object::Error(format!("Can't parse '{}' as a base {} integer.", s, radix)),
+        })
+    });
+    env.define_global("parseInt", Object::Callable(Rc::new(parse_int)));
+
+    let parse_float = NativeCallable::new(Arity::Exact(1), |_, args| {
+        let s: String = args[0].clone().try_into()?;
+        Ok(match s.trim().parse::<f64>() {
+            Ok(n) => Object::from(n),
+            Err(_) => Object::Error(format!("Can't parse '{}' as a number.", s)),
+        })
+    });
+    env.define_global("parseFloat", Object::Callable(Rc::new(parse_float)));
+
+    let is_error = NativeCallable::new(Arity::Exact(1), |_, args| {
+        Ok(Object::Boolean(matches!(args[0], Object::Error(_))))
+    });
+    env.define_global("isError", Object::Callable(Rc::new(is_error)));
+
+    // Unlike parseFloat, text that isn't a number gives nil rather than an
+    // error value, so input can be tested with `num(line) == nil`
+    let num = NativeCallable::new(Arity::Exact(1), |_, args| match &args[0] {
+        Object::String(s) => Ok(s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite())
+            .map_or(Object::Nil, Object::from)),
+        other => Err(format!(
+            "Argument to num must be a string, got a {}.",
+            other.type_name()
+        )
+        .into()),
+    });
+    env.define_global("num", Object::Callable(Rc::new(num)));
+
+    // The text print would show for a value
+    let str = NativeCallable::new(Arity::Exact(1), |interpreter, args| {
+        Ok(Object::String(interpreter.stringify(&args[0])?))
+    });
+    env.define_global("str", Object::Callable(Rc::new(str)));
+
+    // char(n) is the one-character string for code point n; ord is its inverse
+    let char = NativeCallable::new(Arity::Exact(1), |_, args| {
+        let n: f64 = args[0].clone().try_into()?;
+        if n.fract() != 0.0 || !(0.0..=u32::MAX as f64).contains(&n) {
+            return Err("Code point must be a non-negative integer."
+                .to_string()
+                .into());
+        }
+        char::from_u32(n as u32)
+            .map(|c| Object::from(c.to_string()))
+            .ok_or_else(|| format!("{} is not a valid Unicode code point.", n).into())
+    });
+    env.define_global("char", Object::Callable(Rc::new(char)));
+
+    let ord = NativeCallable::new(Arity::Exact(1), |_, args| {
+        let s: String = args[0].clone().try_into()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(Object::from(c as u32 as f64)),
+            _ => Err("Argument to ord must be a single character."
+                .to_string()
+                .into()),
+        }
+    });
+    env.define_global("ord", Object::Callable(Rc::new(ord)));
+
+    // Strings are measured in characters
+    let len = NativeCallable::new(Arity::Exact(1), |_, args| match &args[0] {
+        Object::List(list) => Ok(Object::from(list.borrow().len() as f64)),
+        Object::String(s) => Ok(Object::from(s.chars().count() as f64)),
+        other => Err(format!(
+            "Argument to len must be a list or a string, got a {}.",
+            other.type_name()
+        )
+        .into()),
+    });
+    env.define_global("len", Object::Callable(Rc::new(len)));
+
+    let push = NativeCallable::new(Arity::Exact(2), |_, args| match &args[0] {
+        Object::List(list) => {
+            list.borrow_mut().push(args[1].clone());
+            Ok(Object::Nil)
+        }
+        other => Err(format!(
+            "First argument to push must be a list, got a {}.",
+            other.type_name()
+        )
+        .into()),
+    });
+    env.define_global("push", Object::Callable(Rc::new(push)));
+
+    let pop = NativeCallable::new(Arity::Exact(1), |_, args| match &args[0] {
+        Object::List(list) => list
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| "Can't pop from an empty list.".to_string().into()),
+        other => Err(format!(
+            "Argument to pop must be a list, got a {}.",
+            other.type_name()
+        )
+        .into()),
+    });
+    env.define_global("pop", Object::Callable(Rc::new(pop)));
+
+    // A new list holding the same elements, so pushing to one doesn't change
+    // the other. Elements that are themselves lists are still shared.
+    let copy = NativeCallable::new(Arity::Exact(1), |_, args| match &args[0] {
+        Object::List(list) => Ok(Object::from(list.borrow().clone())),
+        other => Err(format!(
+            "Argument to copy must be a list, got a {}.",
+            other.type_name()
+        )
+        .into()),
+    });
+    env.define_global("copy", Object::Callable(Rc::new(copy)));
+
+    // compose(f, g) is the function x -> f(g(x)), taking whatever g takes
+    let compose = NativeCallable::new(Arity::Exact(2), |_, args| {
+        let (Some(outer), Some(inner)) = (callable(&args[0]), callable(&args[1])) else {
+            return Err("Arguments to compose must be functions.".to_string().into());
+        };
+        if !outer.arity().accepts(1) {
+            return Err("First argument to compose must take one argument."
+                .to_string()
+                .into());
+        }
+        Ok(Object::Callable(Rc::new(Composed::new(outer, inner))))
+    });
+    env.define_global("compose", Object::Callable(Rc::new(compose)));
+
+    // partial(f, a, b) is f with its first two arguments fixed to a and b
+    let partial = NativeCallable::new(Arity::AtLeast(1), |_, mut args| {
+        let func = callable(&args[0])
+            .ok_or_else(|| "First argument to partial must be a function.".to_string())?;
+        let bound = args.split_off(1);
+        let count = bound.len();
+        let partial = Partial::new(Rc::clone(&func), bound).ok_or_else(|| {
+            format!(
+                "Can't bind {} arguments to a function that takes {}.",
+                count,
+                func.arity()
+            )
+        })?;
+        Ok(Object::Callable(Rc::new(partial)))
+    });
+    env.define_global("partial", Object::Callable(Rc::new(partial)));
+
+    // Elements are joined as they print, so strings lose their quotes
+    let join = NativeCallable::new(Arity::Exact(2), |interpreter, args| {
+        let separator: String = args[1].clone().try_into()?;
+        match &args[0] {
+            Object::List(list) => Ok(Object::from(
+                list.borrow()
+                    .clone()
+                    .iter()
+                    .map(|item| interpreter.stringify(item))
+                    .collect::<Result<Vec<String>, _>>()?
+                    .join(&separator),
+            )),
+            other => Err(format!(
+                "First argument to join must be a list, got a {}.",
+                other.type_name()
+            )
+            .into()),
+        }
+    });
+    env.define_global("join", Object::Callable(Rc::new(join)));
+
+    // split(s, delim) or split(s, delim, limit), where limit caps the number of
+    // splits. An empty delimiter splits between characters.
+    let split = NativeCallable::new(Arity::Range(2, 3), |_, args| {
+        let s: String = args[0].clone().try_into()?;
+        let delimiter: String = args[1].clone().try_into()?;
+        let limit = match args.get(2) {
+            Some(limit) => {
+                let limit: f64 = limit.clone().try_into()?;
+                if limit.fract() != 0.0 || limit < 0.0 {
+                    return Err("Limit must be a non-negative integer.".to_string().into());
+                }
+                limit as usize
+            }
+            None => usize::MAX,
+        };
+        let parts: Vec<String> = if delimiter.is_empty() {
+            let mut chars = s.chars();
+            let mut parts: Vec<String> = chars.by_ref().take(limit).map(String::from).collect();
+            let rest: String = chars.collect();
+            if !rest.is_empty() {
+                parts.push(rest);
+            }
+            parts
+        } else {
+            s.splitn(limit.saturating_add(1), delimiter.as_str())
+                .map(String::from)
+                .collect()
+        };
+        Ok(Object::from(
+            parts.into_iter().map(Object::from).collect::<Vec<Object>>(),
+        ))
+    });
+    env.define_global("split", Object::Callable(Rc::new(split)));
+
+    let min = NativeCallable::new(Arity::AtLeast(1), |_, args| {
+        extremum("min", args, |candidate, best| candidate < best)
+    });
+    env.define_global("min", Object::Callable(Rc::new(min)));
+
+    let max = NativeCallable::new(Arity::AtLeast(1), |_, args| {
+        extremum("max", args, |candidate, best| candidate > best)
+    });
+    env.define_global("max", Object::Callable(Rc::new(max)));
+
+    let type_of = NativeCallable::new(Arity::Exact(1), |_, args| {
+        Ok(Object::from(args[0].type_name().to_string()))
+    });
+    env.define_global("type", Object::Callable(Rc::new(type_of)));
+
+    env.define_global("PI", Object::from(std::f64::consts::PI));
+    env.define_global("E", Object::from(std::f64::consts::E));
+
+    // sin, cos and tan take radians
+    let math: [(&str, NativeFn); 7] = [
+        ("sin", |_, args| {
+            Ok(Object::from(f64::try_from(args[0].clone())?.sin()))
+        }),
+        ("cos", |_, args| {
+            Ok(Object::from(f64::try_from(args[0].clone())?.cos()))
+        }),
+        ("tan", |_, args| {
+            Ok(Object::from(f64::try_from(args[0].clone())?.tan()))
+        }),
+        ("sqrt", |_, args| {
+            let x = f64::try_from(args[0].clone())?;
+            if x < 0.0 {
+                return Err("Can't take the square root of a negative number."
+                    .to_string()
+                    .into());
+            }
+            Ok(Object::from(x.sqrt()))
+        }),
+        ("floor", |_, args| {
+            Ok(Object::from(f64::try_from(args[0].clone())?.floor()))
+        }),
+        ("ceil", |_, args| {
+            Ok(Object::from(f64::try_from(args[0].clone())?.ceil()))
+        }),
+        ("abs", |_, args| {
+            Ok(Object::from(f64::try_from(args[0].clone())?.abs()))
+        }),
+    ];
+    for (name, func) in math {
+        let native = NativeCallable::new(Arity::Exact(1), func);
+        env.define_global(name, Object::Callable(Rc::new(native)));
+    }
+
+    let pow = NativeCallable::new(Arity::Exact(2), |_, args| {
+        let base: f64 = args[0].clone().try_into()?;
+        let exponent: f64 = args[1].clone().try_into()?;
+        Ok(Object::from(base.powf(exponent)))
+    });
+    env.define_global("pow", Object::Callable(Rc::new(pow)));
+
+    // input() or input(prompt) reads a line, giving nil at the end of input
+    let input = NativeCallable::new(Arity::Range(0, 1), |interpreter, args| {
+        if let Some(prompt) = args.first() {
+            let prompt = interpreter.stringify(prompt)?;
+            interpreter.write_prompt(&prompt);
+        }
+        match interpreter.read_line() {
+            Ok(line) => Ok(line.map_or(Object::Nil, Object::from)),
+            Err(err) => Err(format!("Can't read input: {}.", err).into()),
+        }
+    });
+    env.define_global("input", Object::Callable(Rc::new(input)));
+
+    // Prints lists with one element per line, indented by nesting depth
+    let pretty_print = NativeCallable::new(Arity::Exact(1), |interpreter, args| {
+        let text = interpreter.pretty(&args[0])?;
+        interpreter.write_line(&text);
+        Ok(Object::Nil)
+    });
+    env.define_global("prettyPrint", Object::Callable(Rc::new(pretty_print)));
+
+    // For use when auto_flush is off
+    let flush = NativeCallable::new(Arity::Exact(0), |interpreter, _| {
+        interpreter.flush_output();
+        Ok(Object::Nil)
+    });
+    env.define_global("flush", Object::Callable(Rc::new(flush)));
+
+    // round(x) to the nearest integer, round(x, digits) to that many decimal places
+    let round = NativeCallable::new(Arity::Range(1, 2), |_, args| {
+        let x: f64 = args[0].clone().try_into()?;
+        let digits: f64 = match args.get(1) {
+            Some(digits) => digits.clone().try_into()?,
+            None => 0.0,
+        };
+        if digits.fract() != 0.0 {
+            return Err("Digits must be an integer.".to_string().into());
+        }
+        let scale = 10f64.powf(digits);
+        // Digit counts too far either way for f64 would give NaN, so leave x be
+        if scale == 0.0 || !scale.is_finite() || !(x * scale).is_finite() {
+            return Ok(Object::from(x));
+        }
+        Ok(Object::from((x * scale).round() / scale))
+    });
+    env.define_global("round", Object::Callable(Rc::new(round)));
+
+    // Inclusive at both ends, ordered like the relational operators
+    let between = NativeCallable::new(Arity::Exact(3), |_, args| {
+        match (&args[0], &args[1], &args[2]) {
+            (Object::Number(x), Object::Number(lo), Object::Number(hi)) => {
+                Ok(Object::from(lo <= x && x <= hi))
+            }
+            (Object::String(x), Object::String(lo), Object::String(hi)) => {
+                Ok(Object::from(lo <= x && x <= hi))
+            }
+            _ => Err("Arguments to between must be all numbers or all strings."
+                .to_string()
+                .into()),
+        }
+    });
+    env.define_global("between", Object::Callable(Rc::new(between)));
+
+    // log(level, message, ...) writes "[LEVEL hh:mm:ss] message ..." to the error stream
+    let log = NativeCallable::new(Arity::AtLeast(2), |interpreter, args| {
+        let level: String = args[0].clone().try_into()?;
+        let message = args[1..]
+            .iter()
+            .map(|arg| interpreter.stringify(arg))
+            .collect::<Result<Vec<String>, _>>()?;
+
+        let seconds = interpreter.now().as_secs() % (24 * 60 * 60);
+        let line = format!(
+            "[{} {:02}:{:02}:{:02}] {}",
+            level.to_uppercase(),
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            message.join(" ")
+        );
+        interpreter.write_error_line(&line);
+        Ok(Object::Nil)
+    });
+    env.define_global("log", Object::Callable(Rc::new(log)));
+}
+
+/// Functions and classes, the values a call expression accepts.
+fn callable(value: &Object) -> Option<Rc<dyn Callable>> {
+    match value {
+        Object::Callable(callable) => Some(Rc::clone(callable)),
+        Object::Class(class) => Some(Rc::clone(class) as Rc<dyn Callable>),
+        _ => None,
+    }
+}
+
+/// Picks the number that `better` prefers over every other argument, or over
+/// every other element when the only argument is a list.
+fn extremum(
+    name: &str,
+    args: Vec<Object>,
+    better: fn(f64, f64) -> bool,
+) -> Result<Object, NativeError> {
+    let args = match args.as_slice() {
+        [Object::List(list)] => list.borrow().clone(),
+        _ => args,
+    };
+    let mut numbers = args.into_iter().map(f64::try_from);
+    let mut best = numbers
+        .next()
+        .ok_or_else(|| format!("Can't take the {} of an empty list.", name))??;
+    for candidate in numbers {
+        let candidate = candidate?;
+        if better(candidate, best) {
+            best = candidate;
+        }
+    }
+    Ok(Object::from(best))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    use crate::class::{LoxClass, LoxInstance};
+    use crate::interpreter::{Interpreter, RuntimeError, Unwind};
+    use crate::test_support::SharedBuffer;
+    use crate::tokens::{Object, Token, TokenType};
+
+    fn runtime_error(result: Result<Object, Unwind>) -> RuntimeError {
+        match result {
+            Err(Unwind::Error(err)) => err,
+            other => panic!("Expected a runtime error, got {:?}", other),
+        }
+    }
+
+    fn call_native(name: &str, args: Vec<Object>) -> Result<Object, Unwind> {
+        let mut interpreter = Interpreter::new();
+        let paren = Token::new(TokenType::RightParen, ")".to_string(), None, 1);
+        match interpreter.globals()[name].clone() {
+            Object::Callable(callable) => callable.call(&mut interpreter, &paren, args),
+            other => panic!("{} is not a native function: {}", name, other),
+        }
+    }
+
+    #[test]
+    fn test_clock_returns_seconds() {
+        match call_native("clock", vec![]).unwrap() {
+            // Somewhere between 2001 and 5138 when counted in seconds
+            Object::Number(seconds) => assert!(seconds > 1e9 && seconds < 1e11),
+            other => panic!("Expected clock() to return a Number, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reverse_string() {
+        assert_eq!(
+            call_native("reverse", vec![Object::String("abc".to_string())]).unwrap(),
+            Object::String("cba".to_string())
+        );
+        assert_eq!(
+            call_native("reverse", vec![Object::String("naïve".to_string())]).unwrap(),
+            Object::String("evïan".to_string())
+        );
+    }
+
+    #[test]
+    fn test_reverse_list() {
+        let numbers = Object::from(vec![
+            Object::Number(1.0),
+            Object::Number(2.0),
+            Object::Number(3.0),
+        ]);
+        let reversed = call_native("reverse", vec![numbers.clone()]).unwrap();
+        assert_eq!(reversed.to_string(), "[3, 2, 1]");
+        // The argument is left as it was
+        assert_eq!(numbers.to_string(), "[1, 2, 3]");
+        assert_ne!(reversed, numbers);
+    }
+
+    #[test]
+    fn test_reverse_rejects_other_types() {
+        let err = runtime_error(call_native("reverse", vec![Object::Number(1.0)]));
+        assert_eq!(
+            err.message,
+            "Argument to reverse must be a list or a string."
+        );
+    }
+
+    #[test]
+    fn test_parse_int_with_radix() {
+        let parse_int = |s: &str, radix: f64| {
+            call_native(
+                "parseInt",
+                vec![Object::String(s.to_string()), Object::Number(radix)],
+            )
+        };
+        assert_eq!(parse_int("ff", 16.0).unwrap(), Object::Number(255.0));
+        assert_eq!(parse_int("101", 2.0).unwrap(), Object::Number(5.0));
+        assert_eq!(parse_int(" -z ", 36.0).unwrap(), Object::Number(-35.0));
+        assert_eq!(
+            parse_int("12", 2.0).unwrap(),
+            Object::Error("Can't parse '12' as a base 2 integer.".to_string())
+        );
+        assert_eq!(
+            runtime_error(parse_int("1", 37.0)).message,
+            "Radix must be an integer between 2 and 36."
+        );
+    }
+
+    #[test]
+    fn test_parse_float() {
+        assert_eq!(
+            call_native("parseFloat", vec![Object::String("2.5".to_string())]).unwrap(),
+            Object::Number(2.5)
+        );
+        assert_eq!(
+            call_native("parseFloat", vec![Object::String("x".to_string())]).unwrap(),
+            Object::Error("Can't parse 'x' as a number.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_error() {
+        let failed = call_native("parseFloat", vec![Object::String("x".to_string())]).unwrap();
+        assert_eq!(failed.to_string(), "<error: Can't parse 'x' as a number.>");
+        assert_eq!(
+            call_native("isError", vec![failed]).unwrap(),
+            Object::Boolean(true)
+        );
+        let parsed = call_native("parseFloat", vec![Object::String("2".to_string())]).unwrap();
+        assert_eq!(
+            call_native("isError", vec![parsed]).unwrap(),
+            Object::Boolean(false)
+        );
+    }
+
+    #[test]
+    fn test_min_max_of_list() {
+        let list = |values: &[f64]| {
+            vec![Object::from(
+                values
+                    .iter()
+                    .map(|n| Object::Number(*n))
+                    .collect::<Vec<Object>>(),
+            )]
+        };
+        assert_eq!(
+            call_native("max", list(&[3.0, 1.0, 2.0])).unwrap(),
+            Object::Number(3.0)
+        );
+        assert_eq!(
+            call_native("min", list(&[5.0, 2.0, 8.0])).unwrap(),
+            Object::Number(2.0)
+        );
+        assert_eq!(
+            runtime_error(call_native("max", list(&[]))).message,
+            "Can't take the max of an empty list."
+        );
+    }
+
+    #[test]
+    fn test_min_max_variadic() {
+        let numbers = |values: &[f64]| values.iter().map(|n| Object::Number(*n)).collect();
+        assert_eq!(
+            call_native("min", numbers(&[5.0, 2.0, 8.0])).unwrap(),
+            Object::Number(2.0)
+        );
+        assert_eq!(
+            call_native("max", numbers(&[3.0, 1.0, 2.0])).unwrap(),
+            Object::Number(3.0)
+        );
+        assert_eq!(
+            call_native("max", numbers(&[-1.0])).unwrap(),
+            Object::Number(-1.0)
+        );
+        assert_eq!(
+            runtime_error(call_native("min", vec![Object::Number(1.0), Object::Nil])).message,
+            "Expected a number but got nil."
+        );
+    }
+
+    #[test]
+    fn test_between() {
+        let between = |x: Object, lo: Object, hi: Object| call_native("between", vec![x, lo, hi]);
+        let n = Object::Number;
+        assert_eq!(
+            between(n(5.0), n(1.0), n(10.0)).unwrap(),
+            Object::Boolean(true)
+        );
+        assert_eq!(
+            between(n(10.0), n(1.0), n(10.0)).unwrap(),
+            Object::Boolean(true)
+        );
+        assert_eq!(
+            between(n(0.0), n(1.0), n(10.0)).unwrap(),
+            Object::Boolean(false)
+        );
+        let s = |s: &str| Object::String(s.to_string());
+        assert_eq!(
+            between(s("b"), s("a"), s("c")).unwrap(),
+            Object::Boolean(true)
+        );
+        assert_eq!(
+            runtime_error(between(n(1.0), s("a"), n(2.0))).message,
+            "Arguments to between must be all numbers or all strings."
+        );
+    }
+
+    #[test]
+    fn test_log_writes_to_error_output() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new()
+            .with_error_output(Box::new(buffer.clone()))
+            .with_clock(|| Duration::from_secs(2 * 86400 + 12 * 3600 + 5 * 60 + 9));
+        let paren = Token::new(TokenType::RightParen, ")".to_string(), None, 1);
+        let Object::Callable(log) = interpreter.globals()["log"].clone() else {
+            panic!("log is not a native function");
+        };
+        let args = vec![
+            Object::String("info".to_string()),
+            Object::String("loaded".to_string()),
+            Object::Number(3.0),
+            Object::String("files".to_string()),
+        ];
+        log.call(&mut interpreter, &paren, args).unwrap();
+        assert_eq!(buffer.contents(), "[INFO 12:05:09] loaded 3 files\n");
+    }
+
+    #[test]
+    fn test_pretty_print_writes_nested_list() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        let paren = Token::new(TokenType::RightParen, ")".to_string(), None, 1);
+        let Object::Callable(pretty_print) = interpreter.globals()["prettyPrint"].clone() else {
+            panic!("prettyPrint is not a native function");
+        };
+        let list = Object::from(vec![
+            Object::Number(1.0),
+            Object::from(vec![Object::Number(2.0), Object::Number(3.0)]),
+        ]);
+        pretty_print
+            .call(&mut interpreter, &paren, vec![list])
+            .unwrap();
+        assert_eq!(buffer.contents(), "[\n  1,\n  [\n    2,\n    3\n  ]\n]\n");
+    }
+
+    #[test]
+    fn test_input_reads_lines_until_eof() {
+        let output = SharedBuffer::default();
+        let mut interpreter = Interpreter::new()
+            .with_output(Box::new(output.clone()))
+            .with_input(Box::new(Cursor::new("first\r\nsecond".as_bytes().to_vec())));
+        let paren = Token::new(TokenType::RightParen, ")".to_string(), None, 1);
+        let Object::Callable(input) = interpreter.globals()["input"].clone() else {
+            panic!("input is not a native function");
+        };
+        let mut read = |args: Vec<Object>| {
+            Rc::clone(&input)
+                .call(&mut interpreter, &paren, args)
+                .unwrap()
+        };
+
+        assert_eq!(
+            read(vec![Object::String("> ".to_string())]),
+            Object::String("first".to_string())
+        );
+        assert_eq!(read(vec![]), Object::String("second".to_string()));
+        assert_eq!(read(vec![]), Object::Nil);
+        assert_eq!(output.contents(), "> ");
+    }
+
+    #[test]
+    fn test_round() {
+        let round =
+            |args: &[f64]| call_native("round", args.iter().map(|n| Object::Number(*n)).collect());
+        assert_eq!(round(&[2.5]).unwrap(), Object::Number(3.0));
+        assert_eq!(round(&[1.23456, 2.0]).unwrap(), Object::Number(1.23));
+        assert_eq!(round(&[1234.0, -2.0]).unwrap(), Object::Number(1200.0));
+        assert_eq!(round(&[1.5, 400.0]).unwrap(), Object::Number(1.5));
+        assert_eq!(round(&[1.2345, -400.0]).unwrap(), Object::Number(1.2345));
+        assert_eq!(round(&[1e300, 10.0]).unwrap(), Object::Number(1e300));
+        assert_eq!(
+            runtime_error(round(&[1.0, 0.5])).message,
+            "Digits must be an integer."
+        );
+    }
+
+    #[test]
+    fn test_math_constants_and_trig() {
+        let globals = Interpreter::new().globals();
+        assert_eq!(globals["PI"], Object::Number(std::f64::consts::PI));
+        assert_eq!(globals["E"], Object::Number(std::f64::consts::E));
+
+        assert_eq!(
+            call_native("sin", vec![Object::Number(0.0)]).unwrap(),
+            Object::Number(0.0)
+        );
+        assert_eq!(
+            call_native("cos", vec![Object::Number(0.0)]).unwrap(),
+            Object::Number(1.0)
+        );
+        assert_eq!(
+            runtime_error(call_native("tan", vec![Object::Nil])).message,
+            "Expected a number but got nil."
+        );
+    }
+
+    #[test]
+    fn test_math_natives() {
+        let number = |name: &str, args: Vec<Object>| call_native(name, args).unwrap();
+        assert_eq!(
+            number("sqrt", vec![Object::Number(9.0)]),
+            Object::Number(3.0)
+        );
+        assert_eq!(
+            number("floor", vec![Object::Number(2.7)]),
+            Object::Number(2.0)
+        );
+        assert_eq!(
+            number("ceil", vec![Object::Number(2.1)]),
+            Object::Number(3.0)
+        );
+        assert_eq!(
+            number("abs", vec![Object::Number(-4.0)]),
+            Object::Number(4.0)
+        );
+        assert_eq!(
+            number("pow", vec![Object::Number(2.0), Object::Number(10.0)]),
+            Object::Number(1024.0)
+        );
+
+        assert_eq!(
+            runtime_error(call_native("sqrt", vec![Object::Number(-1.0)])).message,
+            "Can't take the square root of a negative number."
+        );
+        assert_eq!(
+            runtime_error(call_native("pow", vec![Object::Number(2.0), Object::Nil])).message,
+            "Expected a number but got nil."
+        );
+    }
+
+    #[test]
+    fn test_type_names() {
+        let class = Rc::new(LoxClass::new("Point".to_string(), None, HashMap::new()));
+        let instance = Rc::new(RefCell::new(LoxInstance::new(Rc::clone(&class))));
+        let clock = Interpreter::new().globals()["clock"].clone();
+        let cases = [
+            (Object::Number(1.0), "number"),
+            (Object::String("a".to_string()), "string"),
+            (Object::Boolean(true), "boolean"),
+            (Object::Nil, "nil"),
+            (clock, "function"),
+            (Object::Class(class), "class"),
+            (Object::Instance(instance), "instance"),
+            (Object::Error("oops".to_string()), "error"),
+        ];
+        for (value, name) in cases {
+            assert_eq!(
+                call_native("type", vec![value]).unwrap(),
+                Object::String(name.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn test_num() {
+        let num = |s: &str| call_native("num", vec![Object::String(s.to_string())]).unwrap();
+        assert_eq!(num("2.5"), Object::Number(2.5));
+        assert_eq!(num("  -42\n"), Object::Number(-42.0));
+        assert_eq!(num("abc"), Object::Nil);
+        assert_eq!(num(""), Object::Nil);
+        assert_eq!(num("inf"), Object::Nil);
+        assert_eq!(
+            runtime_error(call_native("num", vec![Object::Number(1.0)])).message,
+            "Argument to num must be a string, got a number."
+        );
+    }
+
+    #[test]
+    fn test_str() {
+        let str = |value: Object| call_native("str", vec![value]).unwrap();
+        assert_eq!(str(Object::Number(42.0)), Object::String("42".to_string()));
+        assert_eq!(
+            str(Object::Boolean(true)),
+            Object::String("true".to_string())
+        );
+        assert_eq!(str(Object::Nil), Object::String("nil".to_string()));
+        assert_eq!(
+            str(Object::String("hi".to_string())),
+            Object::String("hi".to_string())
+        );
+    }
+
+    #[test]
+    fn test_char_and_ord() {
+        let number = |n: f64| vec![Object::Number(n)];
+        let string = |s: &str| vec![Object::String(s.to_string())];
+        assert_eq!(
+            call_native("char", number(65.0)).unwrap(),
+            Object::String("A".to_string())
+        );
+        assert_eq!(
+            call_native("char", number(960.0)).unwrap(),
+            Object::String("π".to_string())
+        );
+        assert_eq!(
+            call_native("ord", string("A")).unwrap(),
+            Object::Number(65.0)
+        );
+        assert_eq!(
+            call_native("ord", string("π")).unwrap(),
+            Object::Number(960.0)
+        );
+
+        assert_eq!(
+            runtime_error(call_native("char", number(0xD800 as f64))).message,
+            "55296 is not a valid Unicode code point."
+        );
+        assert_eq!(
+            runtime_error(call_native("char", number(-1.0))).message,
+            "Code point must be a non-negative integer."
+        );
+        for s in ["", "AB"] {
+            assert_eq!(
+                runtime_error(call_native("ord", string(s))).message,
+                "Argument to ord must be a single character."
+            );
+        }
+    }
+
+    #[test]
+    fn test_list_natives() {
+        let list = Object::List(Rc::new(RefCell::new(vec![Object::Number(1.0)])));
+        let len = |value: &Object| call_native("len", vec![value.clone()]).unwrap();
+
+        assert_eq!(
+            call_native("push", vec![list.clone(), Object::from("two".to_string())]).unwrap(),
+            Object::Nil
+        );
+        assert_eq!(len(&list), Object::Number(2.0));
+        assert_eq!(
+            call_native("pop", vec![list.clone()]).unwrap(),
+            Object::String("two".to_string())
+        );
+        assert_eq!(
+            call_native("pop", vec![list.clone()]).unwrap(),
+            Object::Number(1.0)
+        );
+        assert_eq!(len(&list), Object::Number(0.0));
+        assert_eq!(len(&Object::from("naïve".to_string())), Object::Number(5.0));
+
+        assert_eq!(
+            runtime_error(call_native("pop", vec![list.clone()])).message,
+            "Can't pop from an empty list."
+        );
+        assert_eq!(
+            runtime_error(call_native("len", vec![Object::Nil])).message,
+            "Argument to len must be a list or a string, got a nil."
+        );
+        assert_eq!(
+            runtime_error(call_native("push", vec![Object::Nil, list])).message,
+            "First argument to push must be a list, got a nil."
+        );
+    }
+
+    #[test]
+    fn test_copy() {
+        let inner = Object::from(vec![Object::Number(2.0)]);
+        let original = Object::from(vec![Object::Number(1.0), inner.clone()]);
+        let alias = original.clone();
+        let copy = call_native("copy", vec![original.clone()]).unwrap();
+
+        call_native("push", vec![copy.clone(), Object::Number(3.0)]).unwrap();
+        assert_eq!(copy.to_string(), "[1, [2], 3]");
+        assert_eq!(original.to_string(), "[1, [2]]");
+
+        // Assigning a list only aliases it
+        call_native("push", vec![alias, Object::Number(4.0)]).unwrap();
+        assert_eq!(original.to_string(), "[1, [2], 4]");
+
+        // The copy is shallow, so nested lists are shared
+        call_native("push", vec![inner, Object::Number(5.0)]).unwrap();
+        assert_eq!(copy.to_string(), "[1, [2, 5], 3]");
+
+        assert_eq!(
+            runtime_error(call_native("copy", vec![Object::Nil])).message,
+            "Argument to copy must be a list, got a nil."
+        );
+    }
+
+    #[test]
+    fn test_split() {
+        let strings = |parts: &[&str]| {
+            Object::from(
+                parts
+                    .iter()
+                    .map(|part| Object::from(part.to_string()))
+                    .collect::<Vec<Object>>(),
+            )
+        };
+        let split = |args: &[&str]| {
+            let args = args
+                .iter()
+                .map(|arg| Object::from(arg.to_string()))
+                .collect();
+            call_native("split", args).unwrap()
+        };
+        let split_limit = |s: &str, delimiter: &str, limit: f64| {
+            call_native(
+                "split",
+                vec![
+                    Object::from(s.to_string()),
+                    Object::from(delimiter.to_string()),
+                    Object::Number(limit),
+                ],
+            )
+        };
+
+        // Lists compare by identity, so compare how they print
+        let same = |a: Object, b: Object| assert_eq!(a.to_string(), b.to_string());
+        same(split(&["a,b,c", ","]), strings(&["a", "b", "c"]));
+        same(split(&["abc", ""]), strings(&["a", "b", "c"]));
+        same(split(&["abc", ";"]), strings(&["abc"]));
+        same(split(&["a--b--c", "--"]), strings(&["a", "b", "c"]));
+        same(
+            split_limit("a,b,c", ",", 1.0).unwrap(),
+            strings(&["a", "b,c"]),
+        );
+        same(split_limit("abc", "", 1.0).unwrap(), strings(&["a", "bc"]));
+        same(split_limit("a,b", ",", 0.0).unwrap(), strings(&["a,b"]));
+
+        assert_eq!(
+            runtime_error(split_limit("a,b", ",", -1.0)).message,
+            "Limit must be a non-negative integer."
+        );
+        assert_eq!(
+            runtime_error(call_native(
+                "split",
+                vec![Object::Number(1.0), Object::from(",".to_string())]
+            ))
+            .message,
+            "Expected a string but got 1."
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        let join = |items: Vec<Object>, separator: &str| {
+            call_native(
+                "join",
+                vec![Object::from(items), Object::from(separator.to_string())],
+            )
+        };
+        let numbers = vec![
+            Object::Number(1.0),
+            Object::Number(2.0),
+            Object::Number(3.0),
+        ];
+        assert_eq!(
+            join(numbers, "-").unwrap(),
+            Object::String("1-2-3".to_string())
+        );
+        assert_eq!(join(vec![], ",").unwrap(), Object::String(String::new()));
+        assert_eq!(
+            join(vec![Object::from("a".to_string()), Object::Nil], ", ").unwrap(),
+            Object::String("a, nil".to_string())
+        );
+
+        assert_eq!(
+            runtime_error(call_native(
+                "join",
+                vec![Object::Nil, Object::from(",".to_string())]
+            ))
+            .message,
+            "First argument to join must be a list, got a nil."
+        );
+        assert_eq!(
+            runtime_error(call_native("join", vec![Object::from(vec![]), Object::Nil])).message,
+            "Expected a string but got nil."
+        );
+    }
+}
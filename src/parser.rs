@@ -1,6 +1,6 @@
 use crate::error_reporter::ErrorReporter;
-use crate::expressions::Expr;
-use crate::statements::Stmt;
+use crate::expressions::{Depth, Expr};
+use crate::statements::{PrintFormat, Stmt};
 use crate::tokens::{Object, Token, TokenType};
 
 #[derive(Debug)]
@@ -9,18 +9,39 @@ struct ParseError;
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    // How many function bodies enclose the current position
+    function_depth: usize,
+    // How many loop bodies enclose the current position within this function
+    loop_depth: usize,
+    /// When true, `for` loops are rewritten into `while` loops as in the book,
+    /// rather than parsed into `Stmt::For`. `continue` then skips the increment.
+    pub desugar_for: bool,
     pub error_reporter: ErrorReporter,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
+        // Comments only matter to tooling, never to the grammar
+        let tokens = tokens
+            .into_iter()
+            .filter(|token| token.token_type != TokenType::Comment)
+            .collect();
         Self {
             tokens,
             current: 0,
+            function_depth: 0,
+            loop_depth: 0,
+            desugar_for: false,
             error_reporter: ErrorReporter::new(),
         }
     }
 
+    /// Lets errors quote the offending line of `source`.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.error_reporter = ErrorReporter::with_source(source);
+        self
+    }
+
     pub fn parse(&mut self) -> Vec<Stmt> {
         let mut statements: Vec<Stmt> = Vec::new();
         while !self.is_at_end() {
@@ -36,7 +57,11 @@ impl Parser {
     }
 
     fn declaration(&mut self) -> Option<Stmt> {
-        let result = if self.match_token(&[TokenType::Var]) {
+        let result = if self.match_token(&[TokenType::Class]) {
+            self.class_declaration()
+        } else if self.match_token(&[TokenType::Fun]) {
+            self.function("function")
+        } else if self.match_token(&[TokenType::Var]) {
             self.var_declaration()
         } else {
             self.statement()
@@ -51,6 +76,34 @@ impl Parser {
         }
     }
 
+    fn class_declaration(&mut self) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect class name.")?
+            .clone();
+
+        let superclass = if self.match_token(&[TokenType::Less]) {
+            let superclass = self
+                .consume(TokenType::Identifier, "Expect superclass name.")?
+                .clone();
+            if superclass.lexeme == name.lexeme {
+                self.error(&superclass, "A class can't inherit from itself.");
+            }
+            Some(Box::new(Expr::Variable(superclass, Depth::default())))
+        } else {
+            None
+        };
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+        Ok(Stmt::Class(name, superclass, methods))
+    }
+
     fn statement(&mut self) -> Result<Stmt, ParseError> {
         if self.match_token(&[TokenType::Print]) {
             return self.print_statement();
@@ -61,16 +114,93 @@ impl Parser {
         if self.match_token(&[TokenType::If]) {
             return self.if_statement();
         }
+        if self.match_token(&[TokenType::Unless]) {
+            return self.unless_statement();
+        }
         if self.match_token(&[TokenType::While]) {
             return self.while_statement();
         }
         if self.match_token(&[TokenType::For]) {
             return self.for_statement();
         }
+        if self.match_token(&[TokenType::Loop]) {
+            return self.loop_statement();
+        }
+        if self.match_token(&[TokenType::Exit]) {
+            return self.exit_statement();
+        }
+        if self.match_token(&[TokenType::Assert]) {
+            return self.assert_statement();
+        }
+        if self.match_token(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+        if self.match_token(&[TokenType::Defer]) {
+            return self.defer_statement();
+        }
+        if self.match_token(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_control_statement();
+        }
 
         self.expression_statement()
     }
 
+    fn assert_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let condition = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after assertion.")?;
+        Ok(Stmt::Assert(keyword, Box::new(condition)))
+    }
+
+    fn loop_control_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.loop_depth == 0 {
+            self.error(
+                &keyword,
+                &format!("Can't use '{}' outside of a loop.", keyword.lexeme),
+            );
+        }
+        self.consume(
+            TokenType::Semicolon,
+            &format!("Expect ';' after '{}'.", keyword.lexeme),
+        )?;
+        Ok(match keyword.token_type {
+            TokenType::Break => Stmt::Break(keyword),
+            _ => Stmt::Continue(keyword),
+        })
+    }
+
+    fn defer_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.function_depth == 0 {
+            self.error(&keyword, "Can't use 'defer' outside of a function.");
+        }
+        // The deferred statement runs after any loop around it has finished
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        let statement = self.statement();
+        self.loop_depth = enclosing_loop_depth;
+        Ok(Stmt::Defer(keyword, Box::new(statement?)))
+    }
+
+    /// Parses a statement that `break` and `continue` may appear in.
+    fn loop_body(&mut self) -> Result<Stmt, ParseError> {
+        self.loop_depth += 1;
+        let body = self.statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn exit_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        let code = if !self.check(TokenType::Semicolon) {
+            Some(Box::new(self.expression()?))
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after exit code.")?;
+        Ok(Stmt::Exit(keyword, code))
+    }
+
     fn for_statement(&mut self) -> Result<Stmt, ParseError> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
 
@@ -96,7 +226,16 @@ impl Parser {
         };
         self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
 
-        let body = self.statement()?;
+        let body = self.loop_body()?;
+
+        if !self.desugar_for {
+            return Ok(Stmt::For(
+                initializer.map(Box::new),
+                condition.map(Box::new),
+                increment.map(Box::new),
+                Box::new(body),
+            ));
+        }
 
         // Build from inside out: body -> body + increment -> while loop -> block with initializer
         let body_with_increment = match increment {
@@ -105,10 +244,13 @@ impl Parser {
         };
 
         let while_loop = match condition {
-            Some(condition) => Stmt::While(Box::new(condition), Box::new(body_with_increment)),
+            Some(condition) => {
+                Stmt::While(Box::new(condition), Box::new(body_with_increment), None)
+            }
             None => Stmt::While(
                 Box::new(Expr::Literal(Object::Boolean(true))),
                 Box::new(body_with_increment),
+                None,
             ),
         };
 
@@ -139,10 +281,99 @@ impl Parser {
         ))
     }
 
+    /// `unless (c) a else b` is sugar for `if (!c) a else b`.
+    fn unless_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'unless'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after unless condition.")?;
+
+        let then_branch = self.statement()?;
+        let else_branch = if self.match_token(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+
+        let mut bang = Token::new(TokenType::Bang, "!".to_string(), None, keyword.line);
+        bang.column = keyword.column;
+        Ok(Stmt::If(
+            Box::new(Expr::Unary(bang, Box::new(condition))),
+            Box::new(then_branch),
+            else_branch,
+        ))
+    }
+
+    fn function(&mut self, kind: &str) -> Result<Stmt, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, &format!("Expect {} name.", kind))?
+            .clone();
+        self.consume(
+            TokenType::LeftParen,
+            &format!("Expect '(' after {} name.", kind),
+        )?;
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen) {
+            loop {
+                if params.len() >= 255 {
+                    let token = self.peek().clone();
+                    self.error(&token, "Can't have more than 255 parameters.");
+                }
+                params.push(
+                    self.consume(TokenType::Identifier, "Expect parameter name.")?
+                        .clone(),
+                );
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+
+        self.consume(
+            TokenType::LeftBrace,
+            &format!("Expect '{{' before {} body.", kind),
+        )?;
+        // A loop around the declaration doesn't make break valid inside the body
+        let enclosing_loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+        self.function_depth += 1;
+        let body = self.block();
+        self.function_depth -= 1;
+        self.loop_depth = enclosing_loop_depth;
+        Ok(Stmt::Function(name, params, body?))
+    }
+
+    fn return_statement(&mut self) -> Result<Stmt, ParseError> {
+        let keyword = self.previous().clone();
+        if self.function_depth == 0 {
+            self.error(&keyword, "Can't return from top-level code.");
+        }
+        let value = if !self.check(TokenType::Semicolon) {
+            let mut values = vec![self.expression()?];
+            while self.match_token(&[TokenType::Comma]) {
+                values.push(self.expression()?);
+            }
+            // Several values come back as a list, ready for `var a, b = ...`
+            let value = if values.len() == 1 {
+                values.remove(0)
+            } else {
+                Expr::List(values)
+            };
+            Some(Box::new(value))
+        } else {
+            None
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return(keyword, value))
+    }
+
     fn var_declaration(&mut self) -> Result<Stmt, ParseError> {
         let name = self
             .consume(TokenType::Identifier, "Expect variable name.")?
             .clone();
+        if self.check(TokenType::Comma) {
+            return self.destructuring_declaration(name);
+        }
 
         let initializer = if self.match_token(&[TokenType::Equal]) {
             Some(Box::new(self.expression()?))
@@ -157,26 +388,106 @@ impl Parser {
         Ok(Stmt::Var(name, initializer))
     }
 
+    /// The rest of `var a, b = value;` once the first name has been read.
+    fn destructuring_declaration(&mut self, first: Token) -> Result<Stmt, ParseError> {
+        let mut names = vec![first];
+        while self.match_token(&[TokenType::Comma]) {
+            let name = self
+                .consume(TokenType::Identifier, "Expect variable name.")?
+                .clone();
+            names.push(name);
+        }
+        self.consume(TokenType::Equal, "Expect '=' after variable names.")?;
+        let value = self.expression()?;
+        self.consume(
+            TokenType::Semicolon,
+            "Expect ';' after variable declaration.",
+        )?;
+        Ok(Stmt::Destructure(names, Box::new(value)))
+    }
+
     fn while_statement(&mut self) -> Result<Stmt, ParseError> {
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
         self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
-        let body = self.statement()?;
-        Ok(Stmt::While(Box::new(condition), Box::new(body)))
+        // Only a braced body can take an else, so `if (a) while (b) s; else t;`
+        // still pairs the else with the if
+        let braced = self.check(TokenType::LeftBrace);
+        let body = self.loop_body()?;
+        let else_branch = if braced && self.match_token(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Stmt::While(
+            Box::new(condition),
+            Box::new(body),
+            else_branch,
+        ))
+    }
+
+    /// `loop { ... }` runs until a `break`, and is sugar for `while (true) { ... }`.
+    fn loop_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'loop'.")?;
+        self.loop_depth += 1;
+        let body = self.block();
+        self.loop_depth -= 1;
+        Ok(Stmt::While(
+            Box::new(Expr::Literal(Object::Boolean(true))),
+            Box::new(Stmt::Block(body?)),
+            None,
+        ))
     }
 
     fn print_statement(&mut self) -> Result<Stmt, ParseError> {
+        let format = if self.match_token(&[TokenType::Colon]) {
+            self.print_format()?
+        } else {
+            PrintFormat::Default
+        };
         let value = self.expression()?;
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
-        Ok(Stmt::Print(Box::new(value)))
+        Ok(Stmt::Print(Box::new(value), format))
+    }
+
+    fn print_format(&mut self) -> Result<PrintFormat, ParseError> {
+        let name = self
+            .consume(TokenType::Identifier, "Expect format name after ':'.")?
+            .clone();
+        match name.lexeme.as_str() {
+            "hex" => Ok(PrintFormat::Hex),
+            "bin" => Ok(PrintFormat::Bin),
+            "oct" => Ok(PrintFormat::Oct),
+            _ => Err(self.error(&name, "Unknown print format.")),
+        }
     }
 
     fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
-        let expr = self.expression()?;
+        let mut expr = self.expression()?;
+        if let Expr::Variable(first, _) = &expr
+            && self.check(TokenType::Comma)
+        {
+            expr = self.unpack_assignment(first.clone())?;
+        }
         self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
         Ok(Stmt::Expression(Box::new(expr)))
     }
 
+    /// The rest of `a, b = value` once the first name has been read. Only
+    /// allowed as a statement, as commas already separate call arguments.
+    fn unpack_assignment(&mut self, first: Token) -> Result<Expr, ParseError> {
+        let mut targets = vec![(first, Depth::default())];
+        while self.match_token(&[TokenType::Comma]) {
+            let name = self
+                .consume(TokenType::Identifier, "Expect variable name.")?
+                .clone();
+            targets.push((name, Depth::default()));
+        }
+        self.consume(TokenType::Equal, "Expect '=' after variable names.")?;
+        let value = self.expression()?;
+        Ok(Expr::Unpack(targets, Box::new(value)))
+    }
+
     fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut statements = Vec::new();
         while !self.check(TokenType::RightBrace) && !self.is_at_end() {
@@ -189,20 +500,81 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> Result<Expr, ParseError> {
+        // `print` in expression position prints its operand and evaluates to it
+        if self.match_token(&[TokenType::Print]) {
+            let value = self.assignment()?;
+            return Ok(Expr::Print(Box::new(value)));
+        }
+
         let expr = self.or()?;
         if self.match_token(&[TokenType::Equal]) {
             let equals = self.previous().clone();
             let value = self.assignment()?;
 
             match expr {
-                Expr::Variable(name) => return Ok(Expr::Assignment(name, Box::new(value))),
+                Expr::Variable(name, _) => {
+                    return Ok(Expr::Assignment(name, Box::new(value), Depth::default()));
+                }
+                Expr::Get(object, name) => return Ok(Expr::Set(object, name, Box::new(value))),
+                Expr::Index(object, bracket, index) => {
+                    return Ok(Expr::IndexSet(object, bracket, index, Box::new(value)));
+                }
                 _ => _ = self.error(&equals, "Invalid assignment target"),
             };
         }
+        if self.match_token(&[
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+        ]) {
+            let operator = self.previous().clone();
+            let value = self.assignment()?;
+            return self.compound_assignment(expr, operator, value);
+        }
 
         Ok(expr)
     }
 
+    /// Desugars `name op= value` into `name = name op value`. Field and element
+    /// targets get their own nodes so the object and index are evaluated once.
+    fn compound_assignment(
+        &mut self,
+        target: Expr,
+        operator: Token,
+        value: Expr,
+    ) -> Result<Expr, ParseError> {
+        let (token_type, lexeme) = match operator.token_type {
+            TokenType::PlusEqual => (TokenType::Plus, "+"),
+            TokenType::MinusEqual => (TokenType::Minus, "-"),
+            TokenType::StarEqual => (TokenType::Star, "*"),
+            _ => (TokenType::Slash, "/"),
+        };
+        let mut binary_operator = Token::new(token_type, lexeme.to_string(), None, operator.line);
+        binary_operator.column = operator.column;
+        let value = Box::new(value);
+
+        match target {
+            Expr::Variable(name, _) => {
+                let current = Expr::Variable(name.clone(), Depth::default());
+                let combined = Expr::Binary(Box::new(current), binary_operator, value);
+                Ok(Expr::Assignment(name, Box::new(combined), Depth::default()))
+            }
+            Expr::Get(object, name) => Ok(Expr::CompoundSet(object, name, binary_operator, value)),
+            Expr::Index(object, bracket, index) => Ok(Expr::CompoundIndexSet(
+                object,
+                bracket,
+                index,
+                binary_operator,
+                value,
+            )),
+            target => {
+                self.error(&operator, "Invalid assignment target");
+                Ok(target)
+            }
+        }
+    }
+
     fn or(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.and()?;
         while self.match_token(&[TokenType::Or]) {
@@ -261,7 +633,7 @@ impl Parser {
 
     fn factor(&mut self) -> Result<Expr, ParseError> {
         let mut expr = self.unary()?;
-        while self.match_token(&[TokenType::Slash, TokenType::Star]) {
+        while self.match_token(&[TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous().clone();
             let right = self.unary()?;
             expr = Expr::Binary(Box::new(expr), operator, Box::new(right));
@@ -303,6 +675,17 @@ impl Parser {
         loop {
             if self.match_token(&[TokenType::LeftParen]) {
                 expr = self.finish_call(expr)?;
+            } else if self.match_token(&[TokenType::Dot]) {
+                let name = self
+                    .consume(TokenType::Identifier, "Expect property name after '.'.")?
+                    .clone();
+                expr = Expr::Get(Box::new(expr), name);
+            } else if self.match_token(&[TokenType::LeftBracket]) {
+                let index = self.expression()?;
+                let bracket = self
+                    .consume(TokenType::RightBracket, "Expect ']' after index.")?
+                    .clone();
+                expr = Expr::Index(Box::new(expr), bracket, Box::new(index));
             } else {
                 break;
             }
@@ -324,19 +707,46 @@ impl Parser {
         if self.match_token(&[TokenType::Number, TokenType::String]) {
             return Ok(Expr::Literal(self.previous().literal.clone().unwrap()));
         }
+        if self.match_token(&[TokenType::Super]) {
+            let keyword = self.previous().clone();
+            self.consume(TokenType::Dot, "Expect '.' after 'super'.")?;
+            let method = self
+                .consume(TokenType::Identifier, "Expect superclass method name.")?
+                .clone();
+            return Ok(Expr::Super(keyword, method));
+        }
+        if self.match_token(&[TokenType::This]) {
+            return Ok(Expr::This(self.previous().clone()));
+        }
         if self.match_token(&[TokenType::Identifier]) {
             let token = self.previous().clone();
-            return Ok(Expr::Variable(token));
+            return Ok(Expr::Variable(token, Depth::default()));
         }
         if self.match_token(&[TokenType::LeftParen]) {
             let expr = self.expression()?;
             self.consume(TokenType::RightParen, "Expect ')' after expression.")?;
             return Ok(Expr::Grouping(Box::new(expr)));
         }
+        if self.match_token(&[TokenType::LeftBracket]) {
+            return self.list();
+        }
 
         Err(self.error(&self.peek().clone(), "Expect expression."))
     }
 
+    /// The elements of a list literal, after its `[`. A trailing comma is allowed.
+    fn list(&mut self) -> Result<Expr, ParseError> {
+        let mut items = Vec::new();
+        while !self.check(TokenType::RightBracket) {
+            items.push(self.expression()?);
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.")?;
+        Ok(Expr::List(items))
+    }
+
     fn error(&mut self, token: &Token, message: &str) -> ParseError {
         self.error_reporter.error_at_token(token, message);
         ParseError
@@ -396,12 +806,17 @@ impl Parser {
             }
 
             match self.peek().token_type {
-                TokenType::Class
+                TokenType::Assert
+                | TokenType::Class
+                | TokenType::Defer
+                | TokenType::Exit
                 | TokenType::For
                 | TokenType::Fun
                 | TokenType::If
+                | TokenType::Loop
                 | TokenType::Print
                 | TokenType::Return
+                | TokenType::Unless
                 | TokenType::Var
                 | TokenType::While => return,
                 _ => {}
@@ -415,6 +830,8 @@ impl Parser {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::scanner::Scanner;
+    use crate::test_support::assert_ast_eq;
 
     #[test]
     fn test_parser() {
@@ -457,7 +874,7 @@ mod tests {
                 Box::new(Expr::Literal(Object::Number(3.0))),
             )))),
         )));
-        assert_eq!(statements[0], expected);
+        assert_ast_eq(&statements[0], &expected);
     }
 
     #[test]
@@ -503,6 +920,7 @@ mod tests {
         ];
 
         let mut parser = Parser::new(tokens);
+        parser.desugar_for = true;
         let statements = parser.parse();
 
         // Expected desugared form:
@@ -523,26 +941,160 @@ mod tests {
             // while (i < 3) { print i; i = i + 1; }
             Stmt::While(
                 Box::new(Expr::Binary(
-                    Box::new(Expr::Variable(var_token.clone())),
+                    Box::new(Expr::Variable(var_token.clone(), Depth::default())),
                     Token::new(TokenType::Less, "<".to_string(), None, 1),
                     Box::new(Expr::Literal(Object::Number(3.0))),
                 )),
                 Box::new(Stmt::Block(vec![
                     // print i;
-                    Stmt::Print(Box::new(Expr::Variable(var_token.clone()))),
+                    Stmt::Print(
+                        Box::new(Expr::Variable(var_token.clone(), Depth::default())),
+                        PrintFormat::Default,
+                    ),
                     // i = i + 1;
                     Stmt::Expression(Box::new(Expr::Assignment(
                         var_token.clone(),
                         Box::new(Expr::Binary(
-                            Box::new(Expr::Variable(var_token.clone())),
+                            Box::new(Expr::Variable(var_token.clone(), Depth::default())),
                             Token::new(TokenType::Plus, "+".to_string(), None, 1),
                             Box::new(Expr::Literal(Object::Number(1.0))),
                         )),
+                        Depth::default(),
                     ))),
                 ])),
+                None,
             ),
         ]);
 
-        assert_eq!(statements[0], expected);
+        assert_ast_eq(&statements[0], &expected);
+    }
+
+    #[test]
+    fn test_return_outside_function() {
+        // return 1;
+        let tokens = vec![
+            Token::new(TokenType::Return, "return".to_string(), None, 1),
+            Token::new(
+                TokenType::Number,
+                "1".to_string(),
+                Some(Object::Number(1.0)),
+                1,
+            ),
+            Token::new(TokenType::Semicolon, ";".to_string(), None, 1),
+            Token::new(TokenType::Eof, "".to_string(), None, 1),
+        ];
+
+        let mut parser = Parser::new(tokens);
+        parser.parse();
+        assert!(parser.error_reporter.had_error());
+        assert_eq!(
+            parser.error_reporter.errors()[0].message,
+            "Can't return from top-level code."
+        );
+    }
+
+    #[test]
+    fn test_break_outside_loop() {
+        let mut scanner = Scanner::new("break; while (true) { fun f() { continue; } }".to_string());
+        let mut parser = Parser::new(scanner.scan_tokens());
+        parser.parse();
+        let messages: Vec<&str> = parser
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "Can't use 'break' outside of a loop.",
+                "Can't use 'continue' outside of a loop."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_defer_outside_function() {
+        let mut parser = Parser::new(Scanner::new("defer print 1;".to_string()).scan_tokens());
+        parser.parse();
+        assert_eq!(
+            parser.error_reporter.errors()[0].message,
+            "Can't use 'defer' outside of a function."
+        );
+    }
+
+    #[test]
+    fn test_unless_desugars_to_negated_if() {
+        let mut parser = Parser::new(Scanner::new("unless (x) print 1;".to_string()).scan_tokens());
+        let statements = parser.parse();
+
+        let expected = Stmt::If(
+            Box::new(Expr::Unary(
+                Token::new(TokenType::Bang, "!".to_string(), None, 1).with_column(1),
+                Box::new(Expr::Variable(
+                    Token::new(TokenType::Identifier, "x".to_string(), None, 1).with_column(9),
+                    Depth::default(),
+                )),
+            )),
+            Box::new(Stmt::Print(
+                Box::new(Expr::Literal(Object::Number(1.0))),
+                PrintFormat::Default,
+            )),
+            None,
+        );
+        assert_ast_eq(&statements[0], &expected);
+    }
+
+    #[test]
+    fn test_loop_desugars_to_while_true() {
+        let mut parser = Parser::new(Scanner::new("loop { break; }".to_string()).scan_tokens());
+        let statements = parser.parse();
+        assert!(!parser.error_reporter.had_error());
+
+        let expected = Stmt::While(
+            Box::new(Expr::Literal(Object::Boolean(true))),
+            Box::new(Stmt::Block(vec![Stmt::Break(
+                Token::new(TokenType::Break, "break".to_string(), None, 1).with_column(8),
+            )])),
+            None,
+        );
+        assert_ast_eq(&statements[0], &expected);
+    }
+
+    #[test]
+    fn test_for_loop_node() {
+        let mut parser =
+            Parser::new(Scanner::new("for (;; i = i + 1) print i;".to_string()).scan_tokens());
+        let statements = parser.parse();
+
+        let i = Token::new(TokenType::Identifier, "i".to_string(), None, 1);
+        let expected = Stmt::For(
+            None,
+            None,
+            Some(Box::new(Expr::Assignment(
+                i.clone().with_column(9),
+                Box::new(Expr::Binary(
+                    Box::new(Expr::Variable(i.clone().with_column(13), Depth::default())),
+                    Token::new(TokenType::Plus, "+".to_string(), None, 1).with_column(15),
+                    Box::new(Expr::Literal(Object::Number(1.0))),
+                )),
+                Depth::default(),
+            ))),
+            Box::new(Stmt::Print(
+                Box::new(Expr::Variable(i.with_column(26), Depth::default())),
+                PrintFormat::Default,
+            )),
+        );
+        assert_ast_eq(&statements[0], &expected);
+    }
+
+    #[test]
+    fn test_compound_assignment_needs_target() {
+        let mut parser = Parser::new(Scanner::new("1 += 2;".to_string()).scan_tokens());
+        parser.parse();
+        assert_eq!(
+            parser.error_reporter.errors()[0].message,
+            "Invalid assignment target"
+        );
     }
 }
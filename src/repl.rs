@@ -0,0 +1,194 @@
+use crate::interpreter::{Interpreter, Unwind};
+use crate::parser::Parser;
+use crate::resolver::Resolver;
+use crate::scanner::Scanner;
+use crate::statements::Stmt;
+use crate::tokens::{Token, TokenType};
+
+const HELP: &str = "\
+:env   List defined variables and functions
+:help  Show this message
+:quit  Exit the prompt";
+
+pub enum CommandResult {
+    Output(String),
+    Quit,
+}
+
+pub fn is_command(line: &str) -> bool {
+    line.trim_start().starts_with(':')
+}
+
+pub fn handle_command(interpreter: &Interpreter, line: &str) -> CommandResult {
+    match line.trim() {
+        ":env" => CommandResult::Output(env_command(interpreter)),
+        ":help" => CommandResult::Output(HELP.to_string()),
+        ":quit" => CommandResult::Quit,
+        command => CommandResult::Output(format!(
+            "Unknown command '{}'. Type :help for a list of commands.",
+            command
+        )),
+    }
+}
+
+/// Runs one line of input against the session's interpreter, so globals carry
+/// over from line to line. Errors are reported but leave the session usable.
+/// A line holding a single expression echoes its value, and its trailing
+/// semicolon may be left off. Returns the exit code if the line called `exit`.
+pub fn run_line(interpreter: &mut Interpreter, line: &str) -> Option<i32> {
+    let source = line.trim_end().to_string();
+    let mut scanner = Scanner::new(source.clone());
+    let mut tokens = scanner.scan_tokens();
+    scanner.check_delimiters();
+    if scanner.error_reporter.had_error() {
+        return None;
+    }
+    // Decided on tokens rather than text, so a trailing comment doesn't hide
+    // the last real token
+    let eof = tokens.len() - 1;
+    if eof > 0
+        && !matches!(
+            tokens[eof - 1].token_type,
+            TokenType::Semicolon | TokenType::RightBrace
+        )
+    {
+        let line = tokens[eof].line;
+        tokens.insert(
+            eof,
+            Token::new(TokenType::Semicolon, ";".to_string(), None, line),
+        );
+    }
+    let mut parser = Parser::new(tokens).with_source(&source);
+    let statements = parser.parse();
+    if parser.error_reporter.had_error() {
+        return None;
+    }
+    let mut resolver = Resolver::new().with_source(&source);
+    resolver.resolve(&statements);
+    if resolver.error_reporter.had_error() {
+        return None;
+    }
+
+    let code = if let [Stmt::Expression(_)] = statements.as_slice() {
+        match interpreter.interpret_collect(statements) {
+            Ok((values, code)) => {
+                for value in values {
+                    match interpreter.repr(&value) {
+                        Ok(text) => interpreter.write_line(&text),
+                        Err(Unwind::Error(err)) => {
+                            interpreter.error_reporter.runtime_error(err);
+                            break;
+                        }
+                        Err(Unwind::Exit(code)) => return Some(code),
+                        Err(_) => {
+                            unreachable!("toString runs as a call, which stops other unwinds")
+                        }
+                    }
+                }
+                code
+            }
+            Err(err) => {
+                interpreter.error_reporter.runtime_error(err);
+                None
+            }
+        }
+    } else {
+        interpreter.interpret(statements)
+    };
+    interpreter.error_reporter.clear();
+    code
+}
+
+fn env_command(interpreter: &Interpreter) -> String {
+    let globals = interpreter.globals();
+    let mut names: Vec<&String> = globals.keys().collect();
+    names.sort();
+    names
+        .iter()
+        .map(|name| format!("{} = {}", name, globals[*name].repr()))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::SharedBuffer;
+    use crate::tokens::Object;
+
+    #[test]
+    fn test_env_lists_defined_variable() {
+        let mut interpreter = Interpreter::new();
+        let mut scanner = Scanner::new("var x = 1;".to_string());
+        let mut parser = Parser::new(scanner.scan_tokens());
+        interpreter.interpret(parser.parse());
+
+        match handle_command(&interpreter, ":env") {
+            CommandResult::Output(output) => {
+                assert!(output.lines().any(|line| line == "x = 1"));
+            }
+            CommandResult::Quit => panic!("Expected :env to produce output"),
+        }
+    }
+
+    #[test]
+    fn test_quit_command() {
+        let interpreter = Interpreter::new();
+        assert!(matches!(
+            handle_command(&interpreter, ":quit"),
+            CommandResult::Quit
+        ));
+    }
+
+    #[test]
+    fn test_state_persists_across_lines() {
+        let mut interpreter = Interpreter::new();
+        run_line(&mut interpreter, "var a = 1;");
+        // Neither a parse error nor a runtime error ends the session
+        run_line(&mut interpreter, "var broken = ;");
+        run_line(&mut interpreter, "a + nil;");
+        run_line(&mut interpreter, "var b = a + 1;");
+        assert_eq!(interpreter.globals()["b"], Object::Number(2.0));
+        assert!(!interpreter.error_reporter.had_runtime_error());
+    }
+
+    #[test]
+    fn test_exit_inside_echoed_call_ends_session() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(run_line(&mut interpreter, "fun f() { exit 3; }"), None);
+        assert_eq!(run_line(&mut interpreter, "f()"), Some(3));
+    }
+
+    #[test]
+    fn test_expression_line_echoes_value() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        run_line(&mut interpreter, "2 * 5");
+        run_line(&mut interpreter, "var quiet = 1;");
+        run_line(&mut interpreter, "quiet + 1;");
+        // Echoed strings keep their quotes; printed ones don't
+        run_line(&mut interpreter, "\"hi\"");
+        run_line(&mut interpreter, "print \"hi\";");
+        assert_eq!(buffer.contents(), "10\n2\n\"hi\"\nhi\n");
+    }
+
+    #[test]
+    fn test_semicolon_added_before_trailing_comment() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        run_line(&mut interpreter, "1 + 2 // note");
+        run_line(&mut interpreter, "var a = 4; // note");
+        run_line(&mut interpreter, "a");
+        assert_eq!(buffer.contents(), "3\n4\n");
+    }
+
+    #[test]
+    fn test_echo_uses_to_string_method() {
+        let buffer = SharedBuffer::default();
+        let mut interpreter = Interpreter::new().with_output(Box::new(buffer.clone()));
+        run_line(&mut interpreter, "class P { toString() { return \"p\"; } }");
+        run_line(&mut interpreter, "P()");
+        run_line(&mut interpreter, "[P()]");
+        assert_eq!(buffer.contents(), "p\n[p]\n");
+    }
+}
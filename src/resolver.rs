@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+
+use crate::error_reporter::ErrorReporter;
+use crate::expressions::{Depth, Expr};
+use crate::statements::Stmt;
+use crate::tokens::{Object, Token, TokenType};
+
+/// Works out, before anything runs, which declaration each variable reference
+/// refers to. The scopes mirror the environments the interpreter creates, so
+/// the recorded depth is the number of environments to walk out at runtime.
+pub struct Resolver {
+    // Local scopes only; anything not found here is a global.
+    // The flag records whether the variable's initializer has finished.
+    scopes: Vec<HashMap<String, bool>>,
+    pub error_reporter: ErrorReporter,
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            scopes: Vec::new(),
+            error_reporter: ErrorReporter::new(),
+        }
+    }
+
+    /// Lets errors quote the offending line of `source`.
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.error_reporter = ErrorReporter::with_source(source);
+        self
+    }
+
+    pub fn resolve(&mut self, statements: &[Stmt]) {
+        for statement in statements {
+            self.resolve_stmt(statement);
+        }
+    }
+
+    fn resolve_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Assert(_, condition) => self.resolve_expr(condition),
+            Stmt::Print(expr, _) => self.resolve_expr(expr),
+            Stmt::Block(statements) => {
+                self.begin_scope();
+                self.resolve(statements);
+                self.end_scope();
+            }
+            Stmt::Break(_) | Stmt::Continue(_) => {}
+            Stmt::Defer(_, statement) => self.resolve_stmt(statement),
+            Stmt::Class(name, superclass, methods) => {
+                self.declare(name);
+                self.define(name);
+                if let Some(superclass) = superclass {
+                    self.resolve_expr(superclass);
+                    self.begin_scope();
+                    self.define_keyword("super");
+                }
+                self.begin_scope();
+                self.define_keyword("this");
+                for method in methods {
+                    if let Stmt::Function(_, params, body) = method {
+                        self.resolve_function(params, body);
+                    }
+                }
+                self.end_scope();
+                if superclass.is_some() {
+                    self.end_scope();
+                }
+            }
+            Stmt::Expression(expr) => self.resolve_expr(expr),
+            Stmt::Exit(_, code) => {
+                if let Some(code) = code {
+                    self.resolve_expr(code);
+                }
+            }
+            Stmt::Function(name, params, body) => {
+                // Defined before the body so the function can call itself
+                self.declare(name);
+                self.define(name);
+                self.resolve_function(params, body);
+            }
+            Stmt::If(condition, then_branch, else_branch) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::Return(_, value) => {
+                if let Some(value) = value {
+                    self.resolve_expr(value);
+                }
+            }
+            Stmt::Var(name, initializer) => {
+                self.declare(name);
+                if let Some(initializer) = initializer {
+                    self.resolve_expr(initializer);
+                }
+                self.define(name);
+            }
+            Stmt::Destructure(names, value) => {
+                for name in names {
+                    self.declare(name);
+                }
+                self.resolve_expr(value);
+                for name in names {
+                    self.define(name);
+                }
+            }
+            Stmt::For(initializer, condition, increment, body) => {
+                // Matches the scope the interpreter runs the loop in
+                self.begin_scope();
+                if let Some(initializer) = initializer {
+                    self.resolve_stmt(initializer);
+                }
+                if let Some(condition) = condition {
+                    self.resolve_expr(condition);
+                }
+                if let Some(increment) = increment {
+                    self.resolve_expr(increment);
+                }
+                self.resolve_stmt(body);
+                self.end_scope();
+            }
+            Stmt::While(condition, body, else_branch) => {
+                self.resolve_expr(condition);
+                self.resolve_stmt(body);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+        }
+    }
+
+    fn resolve_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Variable(name, depth) => {
+                let declared_only =
+                    self.scopes.last().and_then(|scope| scope.get(&name.lexeme)) == Some(&false);
+                if declared_only {
+                    self.error_reporter
+                        .error_at_token(name, "Can't read local variable in its own initializer.");
+                }
+                self.resolve_local(name, depth);
+            }
+            Expr::Assignment(name, value, depth) => {
+                self.resolve_expr(value);
+                self.resolve_local(name, depth);
+            }
+            Expr::Unpack(targets, value) => {
+                self.resolve_expr(value);
+                for (name, depth) in targets {
+                    self.resolve_local(name, depth);
+                }
+            }
+            Expr::Binary(left, operator, right) => {
+                self.check_literal_comparison(left, operator, right);
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Logical(left, _, right) => {
+                self.resolve_expr(left);
+                self.resolve_expr(right);
+            }
+            Expr::Call(callee, _, args) => {
+                self.resolve_expr(callee);
+                for arg in args {
+                    self.resolve_expr(arg);
+                }
+            }
+            Expr::CompoundSet(object, _, _, value) => {
+                self.resolve_expr(object);
+                self.resolve_expr(value);
+            }
+            Expr::CompoundIndexSet(object, _, index, _, value) => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::Get(object, _) => self.resolve_expr(object),
+            Expr::Index(object, _, index) => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+            }
+            Expr::IndexSet(object, _, index, value) => {
+                self.resolve_expr(object);
+                self.resolve_expr(index);
+                self.resolve_expr(value);
+            }
+            Expr::List(items) => {
+                for item in items {
+                    self.resolve_expr(item);
+                }
+            }
+            Expr::Set(object, _, value) => {
+                self.resolve_expr(value);
+                self.resolve_expr(object);
+            }
+            Expr::Grouping(expr) | Expr::Unary(_, expr) | Expr::Print(expr) => {
+                self.resolve_expr(expr)
+            }
+            // `this` and `super` can't be shadowed, so they're found by name
+            Expr::Literal(_) | Expr::This(_) | Expr::Super(_, _) => {}
+        }
+    }
+
+    /// Warns about an ordering comparison between two literals that the
+    /// interpreter is bound to reject, such as `1 < "a"`. Anything involving a
+    /// variable or call is left alone, as its type isn't known until runtime.
+    fn check_literal_comparison(&mut self, left: &Expr, operator: &Token, right: &Expr) {
+        if !matches!(
+            operator.token_type,
+            TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual
+        ) {
+            return;
+        }
+        let (Some(left), Some(right)) = (literal_value(left), literal_value(right)) else {
+            return;
+        };
+        let comparable = matches!(
+            (left, right),
+            (Object::Number(_), Object::Number(_)) | (Object::String(_), Object::String(_))
+        );
+        if !comparable {
+            self.error_reporter
+                .warning_at_token(operator, "This comparison will always fail at runtime.");
+        }
+    }
+
+    fn resolve_function(&mut self, params: &[Token], body: &[Stmt]) {
+        // Parameters and body share the call's environment
+        self.begin_scope();
+        for param in params {
+            self.declare(param);
+            self.define(param);
+        }
+        self.resolve(body);
+        self.end_scope();
+    }
+
+    fn resolve_local(&mut self, name: &Token, depth: &Depth) {
+        if let Some(distance) = self
+            .scopes
+            .iter()
+            .rev()
+            .position(|scope| scope.contains_key(&name.lexeme))
+        {
+            depth.set(distance);
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    /// Globals may be redeclared freely, locals only once per scope.
+    fn declare(&mut self, name: &Token) {
+        let Some(scope) = self.scopes.last_mut() else {
+            return;
+        };
+        if scope.insert(name.lexeme.clone(), false).is_some() {
+            self.error_reporter
+                .error_at_token(name, "Already a variable with this name in this scope.");
+        }
+    }
+
+    fn define(&mut self, name: &Token) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.lexeme.clone(), true);
+        }
+    }
+
+    fn define_keyword(&mut self, keyword: &str) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(keyword.to_string(), true);
+        }
+    }
+}
+
+fn literal_value(expr: &Expr) -> Option<&Object> {
+    match expr {
+        Expr::Literal(value) => Some(value),
+        Expr::Grouping(expr) => literal_value(expr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_reporter::DiagnosticKind;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn resolve_errors(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(Scanner::new(source.to_string()).scan_tokens());
+        let statements = parser.parse();
+        assert!(!parser.error_reporter.had_error());
+        let mut resolver = Resolver::new();
+        resolver.resolve(&statements);
+        resolver
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| error.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_literal_comparison_warning() {
+        let mut parser = Parser::new(Scanner::new("1 < \"a\";".to_string()).scan_tokens());
+        let mut resolver = Resolver::new();
+        resolver.resolve(&parser.parse());
+        let warning = &resolver.error_reporter.errors()[0];
+        assert_eq!(
+            warning.message,
+            "This comparison will always fail at runtime."
+        );
+        assert_eq!(warning.kind, DiagnosticKind::Warning);
+        // A warning doesn't stop the program from running
+        assert!(!resolver.error_reporter.had_error());
+
+        assert!(resolve_errors("var x = 1; x < \"a\";").is_empty());
+        assert!(resolve_errors("1 < 2; \"a\" >= \"b\"; 1 == \"a\";").is_empty());
+    }
+
+    #[test]
+    fn test_local_self_initializer() {
+        assert_eq!(
+            resolve_errors("var a = 1; { var a = a; }"),
+            ["Can't read local variable in its own initializer."]
+        );
+        // Globals are looked up at runtime, so this is left to the interpreter
+        assert!(resolve_errors("var b = b;").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_declaration() {
+        assert_eq!(
+            resolve_errors("{ var x = 1; var x = 2; }"),
+            ["Already a variable with this name in this scope."]
+        );
+        assert_eq!(
+            resolve_errors("fun f(a, a) {}"),
+            ["Already a variable with this name in this scope."]
+        );
+        assert!(resolve_errors("var x = 1; var x = 2;").is_empty());
+        assert!(resolve_errors("{ var x = 1; { var x = 2; } }").is_empty());
+    }
+}
@@ -1,91 +1,304 @@
+use std::collections::VecDeque;
+
 use crate::{
-    error_reporter::ErrorReporter,
+    error_reporter::{Diagnostic, ErrorReporter},
     tokens::{Object, Token, TokenType},
 };
 
 pub struct Scanner {
-    source: String,
+    // Held as chars so indexing is O(1) and never splits a multibyte character
+    source: Vec<char>,
     tokens: Vec<Token>,
     pub error_reporter: ErrorReporter,
+    /// Emit `Comment` tokens instead of discarding comments, for tools such as formatters.
+    pub keep_comments: bool,
+    // Longest string or number literal accepted, in source characters with a
+    // string's quotes included. Longer ones are reported instead of scanned.
+    max_literal_length: usize,
+
+    // Tokens and errors scanned but not yet yielded by the iterator
+    pending: VecDeque<Token>,
+    yielded_errors: usize,
+    emitted_eof: bool,
 
     start: usize,
     current: usize,
     line: u32,
+    // Where the line containing `line_scan` begins, advanced lazily for columns
+    line_start: usize,
+    line_scan: usize,
 }
 
 impl Scanner {
     pub fn new(source: String) -> Self {
         Scanner {
-            source,
+            source: source.chars().collect(),
             tokens: Vec::new(),
-            error_reporter: ErrorReporter::new(),
+            error_reporter: ErrorReporter::with_source(&source),
+            keep_comments: false,
+            max_literal_length: 1_000_000,
+            pending: VecDeque::new(),
+            yielded_errors: 0,
+            emitted_eof: false,
             start: 0,
             current: 0,
             line: 1,
+            line_start: 0,
+            line_scan: 0,
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Vec<Token> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token();
+    /// Like `new`, but with a different limit on the length of string and
+    /// number literals than the default of a million characters.
+    pub fn with_max_literal_length(source: String, max_literal_length: usize) -> Self {
+        Scanner {
+            max_literal_length,
+            ..Self::new(source)
         }
+    }
 
-        let token = Token::new(TokenType::Eof, String::new(), None, self.line);
-        self.tokens.push(token);
+    pub fn scan_tokens(&mut self) -> Vec<Token> {
+        // Errors are still recorded on the error reporter, so only keep the tokens
+        self.tokens = self.by_ref().filter_map(Result::ok).collect();
         self.tokens.clone()
     }
 
+    /// Reports unmatched parentheses, braces and brackets in the scanned tokens, giving
+    /// clearer locations than the parser can once it has lost its place.
+    pub fn check_delimiters(&mut self) {
+        let mut open: Vec<&Token> = Vec::new();
+        for token in &self.tokens {
+            let opener = match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket => {
+                    open.push(token);
+                    continue;
+                }
+                TokenType::RightParen => TokenType::LeftParen,
+                TokenType::RightBrace => TokenType::LeftBrace,
+                TokenType::RightBracket => TokenType::LeftBracket,
+                _ => continue,
+            };
+            match open.last() {
+                Some(last) if last.token_type == opener => {
+                    open.pop();
+                }
+                _ => self.error_reporter.error(
+                    token.line,
+                    &format!("Unmatched '{}' on line {}.", token.lexeme, token.line),
+                ),
+            }
+        }
+        for token in open {
+            self.error_reporter.error(
+                token.line,
+                &format!(
+                    "Unmatched '{}' opened on line {}.",
+                    token.lexeme, token.line
+                ),
+            );
+        }
+    }
+
     fn number(&mut self) {
-        while self.peek().is_numeric() {
-            self.advance();
+        if self.char_at(self.start) == '0' && (self.peek() == 'x' || self.peek() == 'X') {
+            self.hex_number();
+            return;
         }
 
+        let mut valid = self.digits();
+
         // Look for a fractional part
-        if self.peek() == '.' && self.peek_next().is_numeric() {
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            self.advance();
+            valid &= self.digits();
+        }
+
+        // Look for an exponent
+        if self.peek() == 'e' || self.peek() == 'E' {
             self.advance();
-            while self.peek().is_numeric() {
+            if self.peek() == '+' || self.peek() == '-' {
                 self.advance();
             }
+            if !self.peek().is_ascii_digit() {
+                self.error_reporter
+                    .error(self.line, "Expect digits in exponent.");
+                return;
+            }
+            valid &= self.digits();
+        }
+
+        if !valid {
+            self.error_reporter
+                .error(self.line, "Digit separator '_' must appear between digits.");
+            return;
+        }
+        if self.literal_too_long("Number") {
+            return;
         }
 
-        let value = self.source[self.start..self.current]
+        match self
+            .text(self.start, self.current)
+            .replace('_', "")
             .parse::<f64>()
-            .unwrap();
-        self.add_literal_token(TokenType::Number, Some(Object::Number(value)));
+        {
+            Ok(value) => self.add_literal_token(TokenType::Number, Some(Object::Number(value))),
+            Err(_) => self
+                .error_reporter
+                .error(self.line, "Invalid number literal."),
+        }
+    }
+
+    /// Consumes a run of digits, allowing single underscores between them.
+    /// Returns false if an underscore wasn't followed by a digit.
+    fn digits(&mut self) -> bool {
+        let mut valid = true;
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
+            if self.advance() == '_' && !self.peek().is_ascii_digit() {
+                valid = false;
+            }
+        }
+        valid
+    }
+
+    fn hex_number(&mut self) {
+        // Skip the x
+        self.advance();
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+
+        let digits = self.text(self.start + 2, self.current);
+        if digits.is_empty() {
+            self.error_reporter
+                .error(self.line, "Expect hex digits after '0x'.");
+            return;
+        }
+        if self.literal_too_long("Number") {
+            return;
+        }
+        match i64::from_str_radix(&digits, 16) {
+            Ok(value) => {
+                self.add_literal_token(TokenType::Number, Some(Object::Number(value as f64)))
+            }
+            Err(_) => self
+                .error_reporter
+                .error(self.line, "Hex literal is too large."),
+        }
     }
 
     fn string(&mut self) {
+        let start_line = self.line;
+        let mut value = String::new();
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let c = self.advance();
+            // Once over the limit the rest of the string is skipped, not kept
+            let keep = self.current - self.start <= self.max_literal_length;
+            match c {
+                '\\' if !self.is_at_end() => {
+                    // Invalid escapes are still reported past the limit
+                    let escaped = self.escape_sequence();
+                    if keep {
+                        value.extend(escaped);
+                    }
+                }
+                '\n' => {
+                    self.line += 1;
+                    if keep {
+                        value.push(c);
+                    }
+                }
+                _ if keep => value.push(c),
+                _ => {}
             }
-            self.advance();
         }
         if self.is_at_end() {
-            self.error_reporter.error(self.line, "Unterminated string.");
+            self.error_reporter
+                .error(start_line, "Unterminated string.");
+            // Resume scanning at the end of the opening line so the rest of the
+            // file is still tokenized and its errors reported.
+            self.current = self.start + 1;
+            self.line = start_line;
+            while self.peek() != '\n' && !self.is_at_end() {
+                self.advance();
+            }
             return;
         }
+        // The closing quote
         self.advance();
+        if self.literal_too_long("String") {
+            return;
+        }
 
-        // Trim the surrounding quotes
-        let value = self.source[self.start + 1..self.current - 1].to_string();
         self.add_literal_token(TokenType::String, Some(Object::String(value)));
     }
 
+    fn escape_sequence(&mut self) -> Option<char> {
+        match self.advance() {
+            'n' => Some('\n'),
+            't' => Some('\t'),
+            'r' => Some('\r'),
+            '\\' => Some('\\'),
+            '"' => Some('"'),
+            '0' => Some('\0'),
+            c => {
+                if c == '\n' {
+                    self.line += 1;
+                }
+                self.error_reporter
+                    .error(self.line, "Invalid escape sequence.");
+                None
+            }
+        }
+    }
+
+    fn block_comment(&mut self) {
+        // Block comments nest, so only the */ matching the opening /* ends the comment
+        let mut depth = 1;
+        while !self.is_at_end() {
+            if self.peek() == '/' && self.peek_next() == '*' {
+                self.advance();
+                self.advance();
+                depth += 1;
+                continue;
+            }
+            if self.peek() == '*' && self.peek_next() == '/' {
+                self.advance();
+                self.advance();
+                depth -= 1;
+                if depth == 0 {
+                    self.add_comment_token();
+                    return;
+                }
+                continue;
+            }
+            if self.peek() == '\n' {
+                self.line += 1;
+            }
+            self.advance();
+        }
+        self.error_reporter
+            .error(self.line, "Unterminated block comment.");
+    }
+
     fn identifier(&mut self) {
         while self.peek().is_alphanumeric() {
             self.advance();
         }
-        let text = self.source[self.start..self.current].to_string();
+        let text = self.text(self.start, self.current);
         let token_type = match text.as_str() {
             "and" => TokenType::And,
+            "assert" => TokenType::Assert,
+            "break" => TokenType::Break,
             "class" => TokenType::Class,
+            "continue" => TokenType::Continue,
+            "defer" => TokenType::Defer,
             "else" => TokenType::Else,
+            "exit" => TokenType::Exit,
             "false" => TokenType::False,
             "for" => TokenType::For,
             "fun" => TokenType::Fun,
             "if" => TokenType::If,
+            "loop" => TokenType::Loop,
             "nil" => TokenType::Nil,
             "or" => TokenType::Or,
             "print" => TokenType::Print,
@@ -93,6 +306,7 @@ impl Scanner {
             "super" => TokenType::Super,
             "this" => TokenType::This,
             "true" => TokenType::True,
+            "unless" => TokenType::Unless,
             "var" => TokenType::Var,
             "while" => TokenType::While,
             _ => TokenType::Identifier,
@@ -107,12 +321,34 @@ impl Scanner {
             ')' => self.add_token(TokenType::RightParen),
             '{' => self.add_token(TokenType::LeftBrace),
             '}' => self.add_token(TokenType::RightBrace),
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
+            ':' => self.add_token(TokenType::Colon),
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
-            '-' => self.add_token(TokenType::Minus),
-            '+' => self.add_token(TokenType::Plus),
+            '-' => {
+                if self.match_token('=') {
+                    self.add_token(TokenType::MinusEqual);
+                } else {
+                    self.add_token(TokenType::Minus);
+                }
+            }
+            '+' => {
+                if self.match_token('=') {
+                    self.add_token(TokenType::PlusEqual);
+                } else {
+                    self.add_token(TokenType::Plus);
+                }
+            }
+            '%' => self.add_token(TokenType::Percent),
             ';' => self.add_token(TokenType::Semicolon),
-            '*' => self.add_token(TokenType::Star),
+            '*' => {
+                if self.match_token('=') {
+                    self.add_token(TokenType::StarEqual);
+                } else {
+                    self.add_token(TokenType::Star);
+                }
+            }
             '!' => {
                 if self.match_token('=') {
                     self.add_token(TokenType::BangEqual);
@@ -146,6 +382,11 @@ impl Scanner {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_comment_token();
+                } else if self.match_token('*') {
+                    self.block_comment();
+                } else if self.match_token('=') {
+                    self.add_token(TokenType::SlashEqual);
                 } else {
                     self.add_token(TokenType::Slash);
                 }
@@ -154,7 +395,9 @@ impl Scanner {
             '\n' => self.line += 1,
             '"' => self.string(),
             _ => {
-                if c.is_numeric() {
+                // Only ASCII digits start a number; other Unicode digits such
+                // as '²' aren't something f64 can parse
+                if c.is_ascii_digit() {
                     self.number();
                 } else if c.is_alphabetic() {
                     self.identifier();
@@ -176,12 +419,16 @@ impl Scanner {
     }
 
     fn char_at(&self, index: usize) -> char {
-        self.source
-            .chars()
-            .nth(index)
+        *self
+            .source
+            .get(index)
             .expect("Tried to scan past the end of source string!")
     }
 
+    fn text(&self, start: usize, end: usize) -> String {
+        self.source[start..end].iter().collect()
+    }
+
     fn peek(&self) -> char {
         if self.is_at_end() {
             '\0'
@@ -212,10 +459,70 @@ impl Scanner {
         self.add_literal_token(token_type, None);
     }
 
+    fn add_comment_token(&mut self) {
+        if self.keep_comments {
+            self.add_token(TokenType::Comment);
+        }
+    }
+
+    /// Reports the literal being scanned if it's over `max_literal_length`.
+    fn literal_too_long(&mut self, kind: &str) -> bool {
+        if self.current - self.start <= self.max_literal_length {
+            return false;
+        }
+        self.error_reporter
+            .error(self.line, &format!("{} literal too long.", kind));
+        true
+    }
+
     fn add_literal_token(&mut self, token_type: TokenType, literal: Option<Object>) {
-        let text = self.source[self.start..self.current].to_string();
-        let token = Token::new(token_type, text, literal, self.line);
-        self.tokens.push(token);
+        let text = self.text(self.start, self.current);
+        let mut token = Token::new(token_type, text, literal, self.line);
+        // A token spanning lines is reported on its last line, where its start column means nothing
+        if !token.lexeme.contains('\n') {
+            token = token.with_column(self.column_at(self.start));
+        }
+        self.pending.push_back(token);
+    }
+
+    fn column_at(&mut self, index: usize) -> u32 {
+        // Token starts only move forward, so each character is looked at once
+        while self.line_scan < index {
+            if self.source[self.line_scan] == '\n' {
+                self.line_start = self.line_scan + 1;
+            }
+            self.line_scan += 1;
+        }
+        (index - self.line_start + 1) as u32
+    }
+}
+
+impl Iterator for Scanner {
+    type Item = Result<Token, Diagnostic>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(error) = self.error_reporter.errors().get(self.yielded_errors) {
+                self.yielded_errors += 1;
+                return Some(Err(error.clone()));
+            }
+            if let Some(token) = self.pending.pop_front() {
+                return Some(Ok(token));
+            }
+            if self.is_at_end() {
+                if self.emitted_eof {
+                    return None;
+                }
+                self.emitted_eof = true;
+                let column = self.column_at(self.current);
+                let token =
+                    Token::new(TokenType::Eof, String::new(), None, self.line).with_column(column);
+                self.pending.push_back(token);
+                continue;
+            }
+            self.start = self.current;
+            self.scan_token();
+        }
     }
 }
 
@@ -250,4 +557,301 @@ mod tests {
         assert_eq!(scanner.tokens[1].token_type, TokenType::BangEqual);
         assert_eq!(scanner.tokens[2].token_type, TokenType::Number);
     }
+
+    #[test]
+    fn test_scanner_resyncs_after_unterminated_string() {
+        let source = String::from("print \"oops;\n@");
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+
+        let errors = scanner.error_reporter.errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].line, 1);
+        assert_eq!(errors[0].message, "Unterminated string.");
+        assert_eq!(errors[1].line, 2);
+        assert_eq!(errors[1].message, "Unexpected character.");
+    }
+
+    #[test]
+    fn test_scanner_iterator_matches_scan_tokens() {
+        let source = "var a = 1;\nprint a + 2;";
+        let iterated: Vec<Token> = Scanner::new(source.to_string())
+            .map(|token| token.unwrap())
+            .collect();
+        let scanned = Scanner::new(source.to_string()).scan_tokens();
+
+        assert_eq!(iterated, scanned);
+        assert_eq!(iterated.last().unwrap().token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_scanner_iterator_yields_errors() {
+        let mut scanner = Scanner::new(String::from("1 @ 2"));
+        assert!(scanner.next().unwrap().is_ok());
+        assert_eq!(
+            scanner.next().unwrap().unwrap_err().message,
+            "Unexpected character."
+        );
+        assert!(scanner.next().unwrap().is_ok());
+        assert_eq!(scanner.next().unwrap().unwrap().token_type, TokenType::Eof);
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn test_scanner_block_comment() {
+        let source = String::from("/* multi\nline */ print 1;");
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.tokens.len(), 4);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Print);
+        assert_eq!(scanner.tokens[0].line, 2);
+        assert_eq!(scanner.tokens[1].token_type, TokenType::Number);
+        assert_eq!(scanner.tokens[2].token_type, TokenType::Semicolon);
+        assert_eq!(scanner.tokens[3].token_type, TokenType::Eof);
+    }
+
+    #[test]
+    fn test_scanner_unterminated_block_comment() {
+        let mut scanner = Scanner::new(String::from("/* never closed"));
+        scanner.scan_tokens();
+
+        assert!(scanner.error_reporter.had_error());
+        assert_eq!(
+            scanner.error_reporter.errors()[0].message,
+            "Unterminated block comment."
+        );
+    }
+
+    #[test]
+    fn test_scanner_nested_block_comment() {
+        let source = String::from("/* a /* b\n */ c */ print 1;");
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+
+        assert!(!scanner.error_reporter.had_error());
+        assert_eq!(scanner.tokens.len(), 4);
+        assert_eq!(scanner.tokens[0].token_type, TokenType::Print);
+        assert_eq!(scanner.tokens[0].line, 2);
+    }
+
+    #[test]
+    fn test_scanner_unbalanced_nested_block_comment() {
+        let mut scanner = Scanner::new(String::from("/* a /* b */ print 1;"));
+        scanner.scan_tokens();
+
+        assert_eq!(
+            scanner.error_reporter.errors()[0].message,
+            "Unterminated block comment."
+        );
+    }
+
+    #[test]
+    fn test_scanner_string_escapes() {
+        let source = String::from(r#""a\tb" "\"q\" \\ \n""#);
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+
+        assert!(!scanner.error_reporter.had_error());
+        assert_eq!(
+            scanner.tokens[0].literal,
+            Some(Object::String("a\tb".to_string()))
+        );
+        assert_eq!(
+            scanner.tokens[1].literal,
+            Some(Object::String("\"q\" \\ \n".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_scanner_invalid_escape() {
+        let mut scanner = Scanner::new(String::from("\n\"bad \\q\";"));
+        scanner.scan_tokens();
+
+        let errors = scanner.error_reporter.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 2);
+        assert_eq!(errors[0].message, "Invalid escape sequence.");
+    }
+
+    #[test]
+    fn test_scanner_scientific_notation() {
+        let mut scanner = Scanner::new(String::from("2.5e3 1E-2 6e+1"));
+        scanner.scan_tokens();
+
+        assert!(!scanner.error_reporter.had_error());
+        assert_eq!(scanner.tokens[0].literal, Some(Object::Number(2500.0)));
+        assert_eq!(scanner.tokens[1].literal, Some(Object::Number(0.01)));
+        assert_eq!(scanner.tokens[2].literal, Some(Object::Number(60.0)));
+    }
+
+    #[test]
+    fn test_scanner_malformed_exponent() {
+        let mut scanner = Scanner::new(String::from("1e;"));
+        scanner.scan_tokens();
+
+        assert_eq!(
+            scanner.error_reporter.errors()[0].message,
+            "Expect digits in exponent."
+        );
+    }
+
+    #[test]
+    fn test_check_delimiters_extra_closing_brace() {
+        let source = String::from("{\n  print 1;\n}\n}");
+        let mut scanner = Scanner::new(source);
+        scanner.scan_tokens();
+        scanner.check_delimiters();
+
+        let errors = scanner.error_reporter.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].line, 4);
+        assert_eq!(errors[0].message, "Unmatched '}' on line 4.");
+    }
+
+    #[test]
+    fn test_check_delimiters_unclosed_paren() {
+        let mut scanner = Scanner::new(String::from("print (1 + 2;"));
+        scanner.scan_tokens();
+        scanner.check_delimiters();
+
+        assert_eq!(
+            scanner.error_reporter.errors()[0].message,
+            "Unmatched '(' opened on line 1."
+        );
+    }
+
+    #[test]
+    fn test_scanner_hex_literals() {
+        let mut scanner = Scanner::new(String::from("0x10 0x1a2b 0XFF 0"));
+        scanner.scan_tokens();
+
+        assert!(!scanner.error_reporter.had_error());
+        assert_eq!(scanner.tokens[0].literal, Some(Object::Number(16.0)));
+        assert_eq!(scanner.tokens[1].literal, Some(Object::Number(6699.0)));
+        assert_eq!(scanner.tokens[2].literal, Some(Object::Number(255.0)));
+        assert_eq!(scanner.tokens[3].literal, Some(Object::Number(0.0)));
+    }
+
+    #[test]
+    fn test_scanner_empty_hex_literal() {
+        let mut scanner = Scanner::new(String::from("0x;"));
+        scanner.scan_tokens();
+
+        assert_eq!(
+            scanner.error_reporter.errors()[0].message,
+            "Expect hex digits after '0x'."
+        );
+    }
+
+    #[test]
+    fn test_scanner_digit_separators() {
+        let mut scanner = Scanner::new(String::from("1_000.5 1_000_000"));
+        scanner.scan_tokens();
+
+        assert!(!scanner.error_reporter.had_error());
+        assert_eq!(scanner.tokens[0].literal, Some(Object::Number(1000.5)));
+        assert_eq!(scanner.tokens[1].literal, Some(Object::Number(1000000.0)));
+    }
+
+    #[test]
+    fn test_scanner_misplaced_digit_separators() {
+        for source in ["_5", "5_", "5__0"] {
+            let mut scanner = Scanner::new(source.to_string());
+            scanner.scan_tokens();
+            assert!(
+                scanner.error_reporter.had_error(),
+                "{} should not scan",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn test_scanner_max_literal_length() {
+        let source = "\"abc\" \"abcd\" 12345 123456 abcdefg 0x1234 \"a\\nb\\tcdef\" 1".to_string();
+        let mut scanner = Scanner::with_max_literal_length(source, 5);
+        let tokens = scanner.scan_tokens();
+
+        let messages: Vec<&str> = scanner
+            .error_reporter
+            .errors()
+            .iter()
+            .map(|error| error.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "String literal too long.",
+                "Number literal too long.",
+                "Number literal too long.",
+                "String literal too long.",
+            ]
+        );
+        // Scanning carries on after the literal that was too long
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        assert_eq!(lexemes, ["\"abc\"", "12345", "abcdefg", "1", ""]);
+    }
+
+    #[test]
+    fn test_scanner_large_input() {
+        // How long this takes is measured by the "large source" benchmark
+        let source = "var a = 1;\n".repeat(100_000);
+        let tokens = Scanner::new(source).scan_tokens();
+
+        assert_eq!(tokens.len(), 5 * 100_000 + 1);
+    }
+
+    #[test]
+    fn test_scanner_multibyte_source() {
+        let mut scanner = Scanner::new(String::from("var π = 3; print \"naïve\";"));
+        scanner.scan_tokens();
+
+        assert!(!scanner.error_reporter.had_error());
+        assert_eq!(scanner.tokens.len(), 9);
+        assert_eq!(scanner.tokens[1].token_type, TokenType::Identifier);
+        assert_eq!(scanner.tokens[1].lexeme, "π");
+        assert_eq!(scanner.tokens[3].literal, Some(Object::Number(3.0)));
+        assert_eq!(
+            scanner.tokens[6].literal,
+            Some(Object::String("naïve".to_string()))
+        );
+        assert_eq!(scanner.tokens[6].lexeme, "\"naïve\"");
+
+        // Unicode digits that aren't ASCII are reported rather than scanned as numbers
+        for source in ["print 1²;", "print ٣;"] {
+            let mut scanner = Scanner::new(source.to_string());
+            scanner.scan_tokens();
+            let messages: Vec<&str> = scanner
+                .error_reporter
+                .errors()
+                .iter()
+                .map(|error| error.message.as_str())
+                .collect();
+            assert_eq!(messages, ["Unexpected character."], "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_scanner_keep_comments() {
+        let source = String::from("// first\nprint 1; /* second */");
+        let mut scanner = Scanner::new(source);
+        scanner.keep_comments = true;
+        scanner.scan_tokens();
+
+        let types: Vec<TokenType> = scanner.tokens.iter().map(|t| t.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::Comment,
+                TokenType::Print,
+                TokenType::Number,
+                TokenType::Semicolon,
+                TokenType::Comment,
+                TokenType::Eof,
+            ]
+        );
+        assert_eq!(scanner.tokens[0].lexeme, "// first");
+        assert_eq!(scanner.tokens[4].lexeme, "/* second */");
+    }
 }
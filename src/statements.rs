@@ -1,11 +1,168 @@
+use std::fmt;
+
 use crate::{expressions::Expr, tokens::Token};
 
+/// Number format selected with `print:<format>`. Only integral numbers are
+/// affected; every other value prints as usual.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum PrintFormat {
+    #[default]
+    Default,
+    Hex,
+    Bin,
+    Oct,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
-    Print(Box<Expr>),
+    Assert(Token, Box<Expr>),
+    Print(Box<Expr>, PrintFormat),
     Block(Vec<Stmt>),
+    Break(Token),
+    /// The superclass is always an `Expr::Variable` and every method is a `Stmt::Function`.
+    Class(Token, Option<Box<Expr>>, Vec<Stmt>),
+    Continue(Token),
+    /// Runs when the enclosing function returns, in the scope it was reached in.
+    Defer(Token, Box<Stmt>),
     Expression(Box<Expr>),
+    Exit(Token, Option<Box<Expr>>),
+    /// Initializer, condition, increment and body. A missing condition loops forever.
+    For(
+        Option<Box<Stmt>>,
+        Option<Box<Expr>>,
+        Option<Box<Expr>>,
+        Box<Stmt>,
+    ),
+    Function(Token, Vec<Token>, Vec<Stmt>),
     If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>),
+    Return(Token, Option<Box<Expr>>),
     Var(Token, Option<Box<Expr>>),
-    While(Box<Expr>, Box<Stmt>),
+    /// `var a, b = value;`, where the value must be a list of exactly as
+    /// many elements as there are names.
+    Destructure(Vec<Token>, Box<Expr>),
+    /// The optional else branch runs when the loop ends because its condition went false.
+    While(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>),
+}
+
+/// Prints statements in the same parenthesized form as expressions.
+impl fmt::Display for Stmt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stmt::Assert(_, condition) => write!(f, "(assert {})", condition),
+            Stmt::Print(expr, PrintFormat::Default) => write!(f, "(print {})", expr),
+            Stmt::Print(expr, format) => {
+                let name = match format {
+                    PrintFormat::Hex => "hex",
+                    PrintFormat::Bin => "bin",
+                    _ => "oct",
+                };
+                write!(f, "(print:{} {})", name, expr)
+            }
+            Stmt::Block(statements) => write!(f, "(block{})", spaced(statements)),
+            Stmt::Break(_) => write!(f, "(break)"),
+            Stmt::Continue(_) => write!(f, "(continue)"),
+            Stmt::Class(name, superclass, methods) => {
+                write!(f, "(class {}", name.lexeme)?;
+                if let Some(superclass) = superclass {
+                    write!(f, " < {}", superclass)?;
+                }
+                write!(f, "{})", spaced(methods))
+            }
+            Stmt::Defer(_, statement) => write!(f, "(defer {})", statement),
+            Stmt::Expression(expr) => write!(f, "(expr {})", expr),
+            Stmt::Exit(_, None) => write!(f, "(exit)"),
+            Stmt::Exit(_, Some(code)) => write!(f, "(exit {})", code),
+            Stmt::For(initializer, condition, increment, body) => {
+                // Missing clauses print as `_`
+                let clause = |clause: Option<String>| clause.unwrap_or_else(|| "_".to_string());
+                write!(
+                    f,
+                    "(for {} {} {} {})",
+                    clause(initializer.as_ref().map(ToString::to_string)),
+                    clause(condition.as_ref().map(ToString::to_string)),
+                    clause(increment.as_ref().map(ToString::to_string)),
+                    body
+                )
+            }
+            Stmt::Function(name, params, body) => {
+                let params: Vec<&str> = params.iter().map(|param| param.lexeme.as_str()).collect();
+                write!(
+                    f,
+                    "(fun {} ({}){})",
+                    name.lexeme,
+                    params.join(" "),
+                    spaced(body)
+                )
+            }
+            Stmt::If(condition, then_branch, None) => {
+                write!(f, "(if {} {})", condition, then_branch)
+            }
+            Stmt::If(condition, then_branch, Some(else_branch)) => {
+                write!(f, "(if-else {} {} {})", condition, then_branch, else_branch)
+            }
+            Stmt::Return(_, None) => write!(f, "(return)"),
+            Stmt::Return(_, Some(value)) => write!(f, "(return {})", value),
+            Stmt::Var(name, None) => write!(f, "(var {})", name.lexeme),
+            Stmt::Var(name, Some(initializer)) => {
+                write!(f, "(var {} = {})", name.lexeme, initializer)
+            }
+            Stmt::Destructure(names, value) => {
+                let names: Vec<&str> = names.iter().map(|name| name.lexeme.as_str()).collect();
+                write!(f, "(var {} = {})", names.join(" "), value)
+            }
+            Stmt::While(condition, body, None) => write!(f, "(while {} {})", condition, body),
+            Stmt::While(condition, body, Some(else_branch)) => {
+                write!(f, "(while-else {} {} {})", condition, body, else_branch)
+            }
+        }
+    }
+}
+
+/// Each statement preceded by a space, ready to follow a node name.
+fn spaced(statements: &[Stmt]) -> String {
+    statements
+        .iter()
+        .map(|statement| format!(" {}", statement))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn dump(source: &str) -> Vec<String> {
+        let mut parser = Parser::new(Scanner::new(source.to_string()).scan_tokens());
+        parser.parse().iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_display_statements() {
+        assert_eq!(dump("print 1 + 2;"), ["(print (+ 1 2))"]);
+        assert_eq!(
+            dump("fun add(a, b) { return a + b; } if (true) add(1, 2); else { var x = 3; }"),
+            [
+                "(fun add (a b) (return (+ a b)))",
+                "(if-else true (expr (call add 1 2)) (block (var x = 3)))",
+            ]
+        );
+        assert_eq!(
+            dump("for (var i = 0; i < 3;) print i;"),
+            ["(for (var i = 0) (< i 3) _ (print i))"]
+        );
+        assert_eq!(
+            dump("print [1, [2, \"three\"],][0];"),
+            ["(print (index (list 1 (list 2 \"three\")) 0))"]
+        );
+        assert_eq!(dump("xs[0] = 1;"), ["(expr (index-set xs 0 1))"]);
+        assert_eq!(dump("x += 4;"), ["(expr x = (+ x 4))"]);
+        assert_eq!(dump("var a, b = f();"), ["(var a b = (call f))"]);
+        assert_eq!(dump("a, b = f();"), ["(expr a, b = (call f))"]);
+        assert_eq!(
+            dump("fun f() { return 1, 2; }"),
+            ["(fun f () (return (list 1 2)))"]
+        );
+        assert_eq!(dump("a.b -= 1;"), ["(expr a.b -= 1)"]);
+        assert_eq!(dump("xs[0] *= 2;"), ["(expr (index*= xs 0 2))"]);
+    }
 }
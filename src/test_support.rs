@@ -0,0 +1,108 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+/// An in-memory writer that can still be read after the interpreter takes it.
+#[derive(Clone, Default)]
+pub struct SharedBuffer {
+    bytes: Rc<RefCell<Vec<u8>>>,
+    flushes: Rc<Cell<usize>>,
+}
+
+impl SharedBuffer {
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.bytes.borrow().clone()).unwrap()
+    }
+
+    pub fn flushes(&self) -> usize {
+        self.flushes.get()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushes.set(self.flushes.get() + 1);
+        Ok(())
+    }
+}
+
+/// Like `assert_eq!`, but on a mismatch reports only the first node where the
+/// two trees differ, along with the nodes enclosing it.
+#[track_caller]
+pub fn assert_ast_eq<T: fmt::Debug + PartialEq>(actual: &T, expected: &T) {
+    if actual != expected {
+        panic!("{}", ast_diff(actual, expected));
+    }
+}
+
+fn ast_diff<T: fmt::Debug>(actual: &T, expected: &T) -> String {
+    let actual = format!("{:#?}", actual);
+    let expected = format!("{:#?}", expected);
+    let actual: Vec<&str> = actual.lines().collect();
+    let expected: Vec<&str> = expected.lines().collect();
+
+    let index = actual
+        .iter()
+        .zip(&expected)
+        .position(|(a, e)| a != e)
+        .unwrap_or(actual.len().min(expected.len()));
+
+    // Walk back up the pretty-printed tree collecting each enclosing node
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let mut path = Vec::new();
+    let mut depth = expected.get(index).map_or(0, |line| indent(line));
+    for line in expected[..index].iter().rev() {
+        if indent(line) < depth {
+            depth = indent(line);
+            path.push(line.trim());
+        }
+    }
+    path.reverse();
+
+    let line_at = |lines: &[&str]| {
+        lines
+            .get(index)
+            .map_or("<end>", |line| line.trim())
+            .to_string()
+    };
+    format!(
+        "ASTs differ\n  in:       {}\n  expected: {}\n  actual:   {}",
+        path.join(" "),
+        line_at(&expected),
+        line_at(&actual)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expressions::Expr;
+    use crate::tokens::{Object, Token, TokenType};
+
+    fn sum(right: f64) -> Expr {
+        Expr::Binary(
+            Box::new(Expr::Literal(Object::Number(1.0))),
+            Token::new(TokenType::Plus, "+".to_string(), None, 1),
+            Box::new(Expr::Literal(Object::Number(right))),
+        )
+    }
+
+    #[test]
+    fn test_ast_diff_points_at_differing_literal() {
+        assert_eq!(
+            ast_diff(&sum(2.0), &sum(3.0)),
+            "ASTs differ\n  in:       Binary( Literal( Number(\n  expected: 3.0,\n  actual:   2.0,"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ASTs differ")]
+    fn test_assert_ast_eq_panics_on_mismatch() {
+        assert_ast_eq(&sum(2.0), &sum(3.0));
+    }
+}
@@ -1,46 +1,242 @@
+use std::convert::Infallible;
 use std::fmt;
 
-use crate::callable::NativeCallable;
+use std::rc::Rc;
+
+use std::cell::RefCell;
+
+use crate::callable::Callable;
+use crate::class::{LoxClass, LoxInstance};
 
 #[rustfmt::skip]
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub enum TokenType {
 
     // Single character tokens
-    LeftParen, RightParen, LeftBrace, RightBrace,
-    Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+    LeftParen, RightParen, LeftBrace, RightBrace, LeftBracket, RightBracket,
+    Colon, Comma, Dot, Minus, Percent, Plus, Semicolon, Slash, Star,
 
     // One or two character tokens
     Bang, BangEqual, Equal, EqualEqual,
     Greater, GreaterEqual, Less, LessEqual,
+    MinusEqual, PlusEqual, SlashEqual, StarEqual,
 
     // Literals
     Identifier, String, Number,
 
     // Keywords
-    And, Class, Else, False, Fun, For, If, Nil, Or,
-    Print, Return, Super, This, True, Var, While,
+    And, Assert, Break, Class, Continue, Defer, Else, Exit, False, Fun, For, If, Loop, Nil, Or,
+    Print, Return, Super, This, True, Unless, Var, While,
+
+    // Only produced when the scanner is keeping comments
+    Comment,
 
     Eof,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Object {
     String(String),
     Number(f64),
     Nil,
     Boolean(bool),
-    NativeFunction(NativeCallable),
+    Callable(Rc<dyn Callable>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+    List(Rc<RefCell<Vec<Object>>>),
+    /// A failure handed back by a native as a value rather than raised, so
+    /// the script can check for it with `isError`.
+    Error(String),
+}
+
+impl Object {
+    /// Like the display form, but with strings in quotes so they can be told
+    /// apart from other values, as in AST dumps and error messages.
+    pub fn repr(&self) -> String {
+        match self {
+            Object::String(s) => format!("\"{}\"", s),
+            other => other.to_string(),
+        }
+    }
+
+    /// The display form, but with each list element on its own line and
+    /// indented two spaces per level of nesting. Empty lists stay as `[]`.
+    pub fn pretty(&self) -> String {
+        let Ok(text) = self.pretty_with(&mut |item| Ok::<_, Infallible>(item.repr()));
+        text
+    }
+
+    /// Builds the display form with `item` rendering each value that isn't a
+    /// list. A top-level string shows as is, but strings inside lists reach
+    /// `item`, which is expected to quote them.
+    pub fn display_with<E>(
+        &self,
+        item: &mut impl FnMut(&Object) -> Result<String, E>,
+    ) -> Result<String, E> {
+        match self {
+            Object::String(s) => Ok(s.clone()),
+            Object::List(list) => list_repr(list, item, &mut Vec::new()),
+            other => item(other),
+        }
+    }
+
+    /// Like `display_with`, but laid out as `pretty` does.
+    pub fn pretty_with<E>(
+        &self,
+        item: &mut impl FnMut(&Object) -> Result<String, E>,
+    ) -> Result<String, E> {
+        match self {
+            Object::String(s) => Ok(s.clone()),
+            Object::List(list) => pretty_list(list, 0, item, &mut Vec::new()),
+            other => item(other),
+        }
+    }
+
+    /// The name of the value's runtime type, as returned by the `type` native.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::String(_) => "string",
+            Object::Number(_) => "number",
+            Object::Boolean(_) => "boolean",
+            Object::Nil => "nil",
+            Object::Callable(_) => "function",
+            Object::Class(_) => "class",
+            Object::Instance(_) => "instance",
+            Object::List(_) => "list",
+            Object::Error(_) => "error",
+        }
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Number(a), Object::Number(b)) => a == b,
+            (Object::Nil, Object::Nil) => true,
+            (Object::Boolean(a), Object::Boolean(b)) => a == b,
+            // Functions are only equal to themselves
+            (Object::Callable(a), Object::Callable(b)) => Rc::ptr_eq(a, b),
+            (Object::Class(a), Object::Class(b)) => Rc::ptr_eq(a, b),
+            (Object::Instance(a), Object::Instance(b)) => Rc::ptr_eq(a, b),
+            (Object::List(a), Object::List(b)) => Rc::ptr_eq(a, b),
+            (Object::Error(a), Object::Error(b)) => a == b,
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for Object {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Object::String(s) => write!(f, "\"{}\"", s),
-            Object::Number(n) => write!(f, "{}", n),
+            Object::String(s) => write!(f, "{}", s),
+            Object::Number(n) => write!(f, "{}", format_number(*n)),
             Object::Boolean(b) => write!(f, "{}", b),
             Object::Nil => write!(f, "nil"),
-            Object::NativeFunction(_) => write!(f, "<native function>"),
+            Object::Callable(callable) => write!(f, "{}", callable),
+            Object::Class(class) => write!(f, "{}", class),
+            Object::Instance(instance) => write!(f, "{}", instance.borrow()),
+            Object::List(_) => {
+                let Ok(text) = self.display_with(&mut |item| Ok::<_, Infallible>(item.repr()));
+                write!(f, "{}", text)
+            }
+            Object::Error(message) => write!(f, "<error: {}>", message),
+        }
+    }
+}
+
+/// `[1, "two", [3]]`, with strings quoted. `seen` holds the lists being
+/// printed further out, so a list that contains itself prints as `[...]`.
+fn list_repr<E>(
+    list: &Rc<RefCell<Vec<Object>>>,
+    item: &mut impl FnMut(&Object) -> Result<String, E>,
+    seen: &mut Vec<*const RefCell<Vec<Object>>>,
+) -> Result<String, E> {
+    if seen.contains(&Rc::as_ptr(list)) {
+        return Ok("[...]".to_string());
+    }
+    seen.push(Rc::as_ptr(list));
+    let mut items = Vec::new();
+    // A copy, as `item` may run Lox code that changes the list
+    for element in list.borrow().clone() {
+        items.push(match &element {
+            Object::List(inner) => list_repr(inner, item, seen)?,
+            other => item(other)?,
+        });
+    }
+    seen.pop();
+    Ok(format!("[{}]", items.join(", ")))
+}
+
+/// Integral values print without a fractional part, so `10 / 2` prints `5`,
+/// while fractions print in full. Magnitudes from 1e21 up, and nonzero ones
+/// below 1e-7, switch to scientific notation rather than spelling out every
+/// digit.
+fn format_number(n: f64) -> String {
+    let magnitude = n.abs();
+    if n.is_finite() && (magnitude >= 1e21 || (magnitude > 0.0 && magnitude < 1e-7)) {
+        format!("{:e}", n)
+    } else if n.fract() == 0.0 {
+        format!("{:.0}", n)
+    } else {
+        n.to_string()
+    }
+}
+
+impl From<f64> for Object {
+    fn from(value: f64) -> Self {
+        Object::Number(value)
+    }
+}
+
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        Object::String(value)
+    }
+}
+
+impl From<Vec<Object>> for Object {
+    fn from(items: Vec<Object>) -> Self {
+        Object::List(Rc::new(RefCell::new(items)))
+    }
+}
+
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        Object::Boolean(value)
+    }
+}
+
+// The error is a plain message so natives can use `?` on these conversions directly.
+impl TryFrom<Object> for f64 {
+    type Error = String;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Number(n) => Ok(n),
+            other => Err(format!("Expected a number but got {}.", other.repr())),
+        }
+    }
+}
+
+impl TryFrom<Object> for String {
+    type Error = String;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::String(s) => Ok(s),
+            other => Err(format!("Expected a string but got {}.", other.repr())),
+        }
+    }
+}
+
+impl TryFrom<Object> for bool {
+    type Error = String;
+
+    fn try_from(value: Object) -> Result<Self, Self::Error> {
+        match value {
+            Object::Boolean(b) => Ok(b),
+            other => Err(format!("Expected a boolean but got {}.", other.repr())),
         }
     }
 }
@@ -51,6 +247,8 @@ pub struct Token {
     pub lexeme: String,
     pub literal: Option<Object>,
     pub line: u32,
+    /// 1-based column of the token's first character, when it came from source.
+    pub column: Option<u32>,
 }
 
 impl Token {
@@ -60,16 +258,175 @@ impl Token {
             lexeme,
             literal,
             line,
+            column: None,
         }
     }
+
+    pub fn with_column(mut self, column: u32) -> Self {
+        self.column = Some(column);
+        self
+    }
+
+    /// The lexeme with control characters escaped, for use in messages.
+    pub fn escaped_lexeme(&self) -> String {
+        let mut escaped = String::new();
+        for c in self.lexeme.chars() {
+            match c {
+                '\t' => escaped.push_str("\\t"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                c if c.is_control() => escaped.extend(c.escape_unicode()),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
 }
 
 impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(literal) = &self.literal {
-            write!(f, "{:?} {} {}", self.token_type, self.lexeme, literal)
+            write!(
+                f,
+                "{:?} {} {}",
+                self.token_type,
+                self.escaped_lexeme(),
+                literal.repr()
+            )
         } else {
-            write!(f, "{:?} {} None", self.token_type, self.lexeme)
+            write!(f, "{:?} {} None", self.token_type, self.escaped_lexeme())
         }
     }
 }
+
+fn pretty_list<E>(
+    list: &Rc<RefCell<Vec<Object>>>,
+    depth: usize,
+    item: &mut impl FnMut(&Object) -> Result<String, E>,
+    seen: &mut Vec<*const RefCell<Vec<Object>>>,
+) -> Result<String, E> {
+    if seen.contains(&Rc::as_ptr(list)) {
+        return Ok("[...]".to_string());
+    }
+    if list.borrow().is_empty() {
+        return Ok("[]".to_string());
+    }
+    seen.push(Rc::as_ptr(list));
+    let indent = "  ".repeat(depth + 1);
+    let mut items = Vec::new();
+    for element in list.borrow().clone() {
+        let text = match &element {
+            Object::List(inner) => pretty_list(inner, depth + 1, item, seen)?,
+            other => item(other)?,
+        };
+        items.push(format!("{}{}", indent, text));
+    }
+    seen.pop();
+    Ok(format!("[\n{}\n{}]", items.join(",\n"), "  ".repeat(depth)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_from_primitives() {
+        assert_eq!(Object::from(1.5), Object::Number(1.5));
+        assert_eq!(
+            Object::from("hi".to_string()),
+            Object::String("hi".to_string())
+        );
+        assert_eq!(Object::from(true), Object::Boolean(true));
+    }
+
+    #[test]
+    fn test_number_display() {
+        assert_eq!(Object::Number(3.0).to_string(), "3");
+        assert_eq!(Object::Number(3.5).to_string(), "3.5");
+        assert_eq!(Object::Number(-0.25).to_string(), "-0.25");
+        assert_eq!(Object::Number(1e20).to_string(), "100000000000000000000");
+        assert_eq!(Object::Number(1e21).to_string(), "1e21");
+        assert_eq!(Object::Number(-2.5e300).to_string(), "-2.5e300");
+        assert_eq!(Object::Number(1e-7).to_string(), "0.0000001");
+        assert_eq!(Object::Number(1e-8).to_string(), "1e-8");
+        assert_eq!(Object::Number(-5e-324).to_string(), "-5e-324");
+        assert_eq!(Object::Number(0.0).to_string(), "0");
+        assert_eq!(Object::Number(f64::INFINITY).to_string(), "inf");
+    }
+
+    #[test]
+    fn test_list_display() {
+        let inner = Object::List(Rc::new(RefCell::new(vec![
+            Object::Number(2.0),
+            Object::String("a".to_string()),
+        ])));
+        let list = Rc::new(RefCell::new(vec![Object::Number(1.0), inner, Object::Nil]));
+        assert_eq!(
+            Object::List(Rc::clone(&list)).to_string(),
+            "[1, [2, \"a\"], nil]"
+        );
+
+        // A list holding itself must not recurse forever
+        list.borrow_mut().push(Object::List(Rc::clone(&list)));
+        assert_eq!(
+            Object::List(Rc::clone(&list)).to_string(),
+            "[1, [2, \"a\"], nil, [...]]"
+        );
+        // Break the cycle so the list is freed
+        list.borrow_mut().clear();
+    }
+
+    #[test]
+    fn test_pretty_list() {
+        let list = Rc::new(RefCell::new(vec![
+            Object::Number(1.0),
+            Object::from(vec![Object::from("a".to_string()), Object::from(vec![])]),
+        ]));
+        assert_eq!(
+            Object::List(Rc::clone(&list)).pretty(),
+            "[\n  1,\n  [\n    \"a\",\n    []\n  ]\n]"
+        );
+        assert_eq!(Object::from("a".to_string()).pretty(), "a");
+
+        list.borrow_mut().push(Object::List(Rc::clone(&list)));
+        assert!(
+            Object::List(Rc::clone(&list))
+                .pretty()
+                .ends_with("  [...]\n]")
+        );
+        list.borrow_mut().clear();
+    }
+
+    #[test]
+    fn test_object_try_into_primitives() {
+        let n: f64 = Object::Number(2.0).try_into().unwrap();
+        assert_eq!(n, 2.0);
+        let s: String = Object::String("hi".to_string()).try_into().unwrap();
+        assert_eq!(s, "hi");
+        let b: bool = Object::Boolean(false).try_into().unwrap();
+        assert!(!b);
+    }
+
+    #[test]
+    fn test_object_try_into_wrong_type() {
+        assert_eq!(
+            f64::try_from(Object::Nil).unwrap_err(),
+            "Expected a number but got nil."
+        );
+        assert_eq!(
+            String::try_from(Object::Number(1.0)).unwrap_err(),
+            "Expected a string but got 1."
+        );
+        assert_eq!(
+            bool::try_from(Object::String("x".to_string())).unwrap_err(),
+            "Expected a boolean but got \"x\"."
+        );
+    }
+
+    #[test]
+    fn test_escaped_lexeme() {
+        let token = Token::new(TokenType::String, "\"a\tb\u{7}é\"".to_string(), None, 1);
+        assert_eq!(token.escaped_lexeme(), "\"a\\tb\\u{7}é\"");
+        assert_eq!(token.to_string(), "String \"a\\tb\\u{7}é\" None");
+    }
+}